@@ -1,44 +1,98 @@
-use std::{num::NonZeroU32, time::Instant};
+use std::{fmt, time::Instant};
 
-use gl_rs::{types::GLint, GetIntegerv, FRAMEBUFFER_BINDING};
-use rand::{rngs::StdRng, Rng, SeedableRng};
-use skia_safe::{font_style::Width, gpu::{self, backend_render_targets, gl::{self, FramebufferInfo, UInt}, SurfaceOrigin}, Color, Color4f, ColorType, Paint, Surface};
-use winit::{application::ApplicationHandler, dpi::PhysicalSize, event::{Modifiers, WindowEvent}, event_loop::EventLoop, raw_window_handle::HasWindowHandle, window::WindowAttributes};
+use skia_safe::Color;
+use winit::{application::ApplicationHandler, dpi::PhysicalSize, event::{ElementState, Modifiers, MouseButton, WindowEvent}, keyboard::Key, event_loop::{ControlFlow, EventLoop}};
 
-use crate::gui::layout::LayoutElement;
-
-use glutin::{
-  config::ConfigTemplateBuilder,
-  context::{ContextApi, ContextAttributesBuilder, PossiblyCurrentContext, Version},
-  display::GetGlDisplay,
-  prelude::*,
-  surface::{Rect, Surface as GlutinSurface, SurfaceAttributesBuilder, WindowSurface},
-};
+use crate::{backend::{self, paint, Backend, GlBackend}, damage::{Damage, DamageTracker}, scheduler::{FrameScheduler, RedrawHandle}, stats::FrameStats, gui::{fonts::{FontError, FontManager}, image::{ImageCache, ImageError}, layout::LayoutElement, text::{FontSpec, TextContext}, theme::Theme}, user_event::UserEvent};
 
 use winit::event_loop::{ActiveEventLoop, EventLoopBuilder };
 use winit::window::Window;
 
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 3.0;
+
+const OVERLAY_ORIGIN: (f32, f32) = (8.0, 8.0);
+
+
+/* Layout unit for a font line height and zoom, never smaller than a pixel */
+fn unit_size(line_height : f32, zoom : f32) -> u32 {
+  ((line_height * zoom).round() as u32).max(1)
+}
+
+/* Position in logical pixels of a physical cursor position */
+fn to_logical(position : (f32, f32), scale_factor : f32) -> (f32, f32) {
+  (position.0 / scale_factor, position.1 / scale_factor)
+}
+
+
+/* Reasons the application can't start */
+#[derive(Debug)]
+pub enum Error {
+  Font(FontError),
+  Image(ImageError),
+}
+
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Error::Font(error) => error.fmt(f),
+      Error::Image(error) => error.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Error::Font(error) => Some(error),
+      Error::Image(error) => Some(error),
+    }
+  }
+}
+
+impl From<FontError> for Error {
+  fn from(error: FontError) -> Self { Error::Font(error) }
+}
+
+impl From<ImageError> for Error {
+  fn from(error: ImageError) -> Self { Error::Image(error) }
+}
+
+
 pub struct Application {
 
   state : Option<ApplicationState>,
   
-  layout : Option<Box<dyn LayoutElement>>
+  layout : Option<Box<dyn LayoutElement>>,
+
+  themes : Vec<Theme>,
+  active_theme : Option<usize>,
+
+  fonts : FontManager,
+  images : ImageCache,
+
+  base_font : FontSpec, // its line height is the layout unit at a scale of 1
+  zoom : f32,
+  unit_size : u32,
+  text : TextContext, // collection of the font manager, scaled by the zoom
+
+  scheduler : FrameScheduler,
+  damage : DamageTracker,
+  stats : FrameStats,
+  overlay : bool, // frame statistics graph drawn over the layout
+
+  cursor : Option<(f32, f32)>,
+  press_target : Option<usize>, // element the current press started on, in paint order
 }
 
 
 struct ApplicationState {
-  surface: Surface,
-  gl_surface: GlutinSurface<WindowSurface>,
-  gr_context: skia_safe::gpu::DirectContext,
-  gl_context: PossiblyCurrentContext,
   window: Window,
+  backend: GlBackend,
 
-  fb_info: FramebufferInfo,
-  num_samples: usize,
-  stencil_size: usize,
   modifiers: Modifiers,
-  frame: usize,
-  previous_frame_start: Instant,
 }
 
 impl ApplicationHandler for Application {
@@ -49,101 +103,17 @@ impl ApplicationHandler for Application {
         .with_inner_size(PhysicalSize::new(1280, 780))
         .with_visible(true);
 
-      let template = ConfigTemplateBuilder::new();
-      let (window, gl_config)  = glutin_winit::DisplayBuilder::new()
-          .with_window_attributes(Some(attributes))
-          .build(event_loop, template, |mut configs| configs.next().unwrap())
-          .unwrap();
-
-      let window = window.unwrap();
-      let window_handle = window.window_handle().unwrap().as_raw();
-
-      let gl_display = gl_config.display();
-  
-      let context_attributes = ContextAttributesBuilder::new()
-        .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
-        .build(Some(window_handle));
-
-      let not_current_gl_context = unsafe {
-        gl_display.create_context(&gl_config, &context_attributes).unwrap()
-      };
-
-      let attrs = SurfaceAttributesBuilder::<WindowSurface>::new().build(
-          window_handle,
-          NonZeroU32::new(window.inner_size().width).unwrap(),
-          NonZeroU32::new(window.inner_size().height).unwrap(),
-      );
-      
-      
-      let gl_surface = unsafe { gl_display.create_window_surface(&gl_config, &attrs).unwrap() };
-      
-      let gl_context = not_current_gl_context.make_current(&gl_surface).unwrap();
-      
-
-      gl_rs::load_with(|s| {
-          let cstr = std::ffi::CString::new(s).unwrap();
-          gl_display.get_proc_address(&cstr)
-      });
-  
-
-      let interface = gpu::gl::Interface::new_load_with(|s| {
-          let cstr = std::ffi::CString::new(s).unwrap();
-          gl_display.get_proc_address(&cstr) as _
-      }).unwrap();
-
-      let mut gr_context  = gpu::direct_contexts::make_gl(interface, None).unwrap();
-       // Framebuffer info
-      
-      let fb_info = {
-        let mut fboid: GLint = 0;
-        unsafe { GetIntegerv(FRAMEBUFFER_BINDING, &mut fboid) };
-
-        FramebufferInfo {
-            fboid: fboid.try_into().unwrap(),
-            format: skia_safe::gpu::gl::Format::RGBA8.into(),
-            ..Default::default()
-        }
-      };
-      // Get surface dimensions
-      let size = window.inner_size();
-      let backend_render_target = gpu::backend_render_targets::make_gl(
-          (size.width as i32, size.height as i32),
-          0, // sample count
-          0, // stencil bits
-          fb_info,
-      );
-
-      // Create Skia surface
-      let mut surface = gpu::surfaces::wrap_backend_render_target(
-          &mut gr_context,
-          &backend_render_target,
-          gpu::SurfaceOrigin::BottomLeft,
-          ColorType::RGBA8888,
-          None,
-          None,
-      )
-      .unwrap();
-
-      let canvas = surface.canvas();
-      
-      window.request_redraw();
+      let (window, backend) = GlBackend::new(event_loop, attributes);
 
       let state = ApplicationState {
-        window: window,
-        surface: surface,
-        gl_context: gl_context,
-        gl_surface: gl_surface,
-        gr_context: gr_context,
-
-        fb_info: fb_info,
-        num_samples: gl_config.num_samples() as usize,
-        stencil_size: gl_config.stencil_size() as usize,
+        window,
+        backend,
+
         modifiers: Modifiers::default(),
-        frame: 0,
-        previous_frame_start: Instant::now(),
       };
 
       self.state = Some(state);
+      self.update_unit_size();
     }
 
     fn window_event(
@@ -154,34 +124,75 @@ impl ApplicationHandler for Application {
     ) {
       match event {
         WindowEvent::CloseRequested => event_loop.exit(),
-        WindowEvent::Resized(size) => {
+        WindowEvent::Resized(size) => self.resize(size),
+
+        WindowEvent::ScaleFactorChanged { .. } => {
+          // The physical size changes along with the factor, the surface has to match it before the next frame
+          if let Some(state) = &self.state {
+            let size = state.window.inner_size();
+            self.resize(size);
+          }
+          self.update_unit_size();
+        }
+
+        WindowEvent::CursorMoved { position, .. } => {
+          let (x, y) = to_logical((position.x as f32, position.y as f32), self.scale_factor());
+          self.cursor = Some((x, y));
+          self.dispatch(UserEvent::MouseMoved(x, y));
+        }
 
-          if self.state.is_none() { return }
-          
-          let (width, height): (u32, u32) = size.into();
+        WindowEvent::CursorLeft { .. } => {
+          self.cursor = None;
+          self.dispatch(UserEvent::MouseLeft);
+        }
 
-          let new_skia_surface = self.create_surface(width, height);
-          let state = self.state.as_mut().unwrap();
-          state.surface = new_skia_surface;
+        WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+          let Some((x, y)) = self.cursor else { return };
+          match state {
+            ElementState::Pressed => self.dispatch(UserEvent::Press(x, y)),
+            ElementState::Released => self.dispatch(UserEvent::Release(x, y)),
+          }
+        }
 
-          /* First resize the opengl drawable */
+        WindowEvent::Focused(false) => self.dispatch(UserEvent::MouseLeft),
 
-          state.gl_surface.resize(
-              &state.gl_context,
-              NonZeroU32::new(width.max(1)).unwrap(),
-              NonZeroU32::new(height.max(1)).unwrap(),
-          );
+        WindowEvent::ModifiersChanged(modifiers) => {
+          if let Some(state) = self.state.as_mut() { state.modifiers = modifiers; }
+        }
 
-          self.state.as_mut().unwrap().window.request_redraw();
+        WindowEvent::KeyboardInput { event, .. } => {
+          let Some(state) = self.state.as_ref() else { return };
+          if event.state != ElementState::Pressed || !state.modifiers.state().control_key() { return }
+
+          let Key::Character(key) = &event.logical_key else { return };
+          match key.as_str() {
+            // Ctrl+T cycles through the registered themes
+            "t" if !self.themes.is_empty() => {
+              let next = self.active_theme.map_or(0, |i| (i + 1) % self.themes.len());
+              self.activate_theme(next);
+            }
+            // Ctrl+F shows the frame statistics
+            "f" => self.set_stats_overlay(!self.overlay),
+            // Ctrl +/- zooms the whole interface, Ctrl+0 resets it
+            "+" | "=" => self.set_zoom(self.zoom * ZOOM_STEP),
+            "-" => self.set_zoom(self.zoom / ZOOM_STEP),
+            "0" => self.set_zoom(1.0),
+            _ => {}
+          }
         }
 
-        WindowEvent::RedrawRequested => {
-          self.update();
-          self.state.as_mut().unwrap().window.request_redraw();
-        },
+        WindowEvent::RedrawRequested => self.update(),
         _ => ()
       }
     }
+
+    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+      // All pending events are handled, the loop sleeps afterwards unless a frame is due
+      let Some(state) = &self.state else { return };
+      if self.scheduler.due() {
+        state.window.request_redraw();
+      }
+    }
 }
 
 
@@ -190,83 +201,351 @@ impl Application {
   
 
   
-  pub fn new<T : LayoutElement + 'static>(layout : T) -> Self { Application { state: None, layout: Some(Box::new(layout))} }
+  pub fn new<T : LayoutElement + 'static>(layout : T) -> Self {
+    let fonts = FontManager::new();
+    let text = TextContext::new(fonts.collection());
+    Application {
+      state: None,
+      layout: Some(Box::new(layout)),
+
+      themes: Vec::new(),
+      active_theme: None,
+
+      fonts,
+      images: ImageCache::new(),
+
+      base_font: FontSpec::default(),
+      zoom: 1.0,
+      unit_size: 1,
+      text,
+
+      scheduler: FrameScheduler::new(),
+      damage: DamageTracker::new(),
+      stats: FrameStats::new(),
+      overlay: false,
+
+      cursor: None,
+      press_target: None,
+    }
+  }
+
+  /* Registers a theme and makes it the active one */
+  pub fn theme(mut self, theme : Theme) -> Self {
+    self.themes.push(theme);
+    self.activate_theme(self.themes.len() - 1);
+    self
+  }
+
+  /* Registers themes without activating them, they can be switched to with set_theme or Ctrl+T */
+  pub fn themes(mut self, themes : impl IntoIterator<Item = Theme>) -> Self {
+    self.themes.extend(themes);
+    self
+  }
+
+  /* Makes the registered font families available to the text elements by family name */
+  pub fn fonts(mut self, fonts : FontManager) -> Self {
+    self.text.collection = fonts.collection();
+    self.fonts = fonts;
+    self
+  }
+
+  pub fn font_manager(&self) -> &FontManager {
+    &self.fonts
+  }
+
+  /* Decoded images shared by all image elements */
+  pub fn images(&mut self) -> &mut ImageCache {
+    &mut self.images
+  }
+
+  /* Font whose line height defines the layout unit, text sizes are relative to it when zooming */
+  pub fn base_font(mut self, spec : FontSpec) -> Self {
+    self.base_font = spec;
+    self
+  }
+
+  pub fn zoom(&self) -> f32 {
+    self.zoom
+  }
+
+  pub fn set_zoom(&mut self, zoom : f32) {
+    self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    self.update_unit_size();
+  }
+
+  /* Lets other threads request a frame, e.g. after new data arrived */
+  pub fn redraw_handle(&self) -> RedrawHandle {
+    self.scheduler.handle()
+  }
 
-  pub fn run(mut self) {
+  /* Number of frames drawn so far, frames are only drawn when something changed */
+  pub fn frames(&self) -> u64 {
+    self.scheduler.frames()
+  }
+
+  /* Timings of the last frames */
+  pub fn stats(&self) -> &FrameStats {
+    &self.stats
+  }
+
+  /* Shows a graph of the frame times over the layout, it can be toggled with Ctrl+F */
+  pub fn stats_overlay(mut self, show : bool) -> Self {
+    self.overlay = show;
+    self
+  }
+
+  pub fn set_stats_overlay(&mut self, show : bool) {
+    self.overlay = show;
+    self.damage.reset(); // the layout below the graph has to be painted again
+    self.scheduler.request();
+  }
+
+  /* Size of one layout unit in logical pixels */
+  pub fn unit_size(&self) -> u32 {
+    self.unit_size
+  }
+
+  /* Physical pixels per logical pixel of the window's monitor */
+  pub fn scale_factor(&self) -> f32 {
+    self.state.as_ref().map_or(1.0, |state| state.window.scale_factor() as f32)
+  }
+
+  /* Derives the unit from the base font and the zoom and hands it to every element, the scale factor is applied when drawing */
+  fn update_unit_size(&mut self) {
+    self.text.scale = self.zoom;
+    self.unit_size = unit_size(self.base_font.line_height(&self.text.collection), self.zoom);
 
     if let Some(layout) = &mut self.layout {
-      for child in layout.iter_mut()  {
-        child.update_unit_size(10);
+      for child in layout.iter_mut() {
+        child.update_unit_size(self.unit_size);
       }
     }
 
+    self.scheduler.request();
+  }
+
+  pub fn active_theme(&self) -> Option<&Theme> {
+    self.active_theme.map(|i| &self.themes[i])
+  }
+
+  /* Switches to the registered theme with the given name, returns false if there is none */
+  pub fn set_theme(&mut self, name : &str) -> bool {
+    let Some(index) = self.themes.iter().position(|t| t.name == name) else { return false };
+    self.activate_theme(index);
+    true
+  }
+
+  fn activate_theme(&mut self, index : usize) {
+    self.active_theme = Some(index);
+    self.damage.reset(); // the clear colour changes as well
+
+    if let Some(layout) = &mut self.layout {
+      self.themes[index].apply(layout.as_mut());
+    }
+
+    self.scheduler.request();
+  }
+
+  /* Fails before opening the window if an element requests a font family that can't be resolved or an image that can't be loaded */
+  pub fn run(mut self) -> Result<(), Error> {
+
+    if let Some(layout) = &mut self.layout {
+      self.fonts.check(layout.as_ref())?;
+
+      let redraw = self.scheduler.handle();
+      for element in layout.iter_mut() {
+        element.load_images(&mut self.images)?;
+        element.connect(&redraw);
+      }
+    }
+    self.update_unit_size();
+
     let event_loop = EventLoopBuilder::default().build().unwrap();
+    event_loop.set_control_flow(ControlFlow::Wait);
+    self.scheduler.connect(event_loop.create_proxy());
     event_loop.run_app(&mut self).unwrap();
+    Ok(())
+  }
+
+  /* Updates the interaction state (hover, press, focus) of the elements */
+  pub fn dispatch(&mut self, event : UserEvent) {
+    let Some(layout) = &mut self.layout else { return };
+
+    match event {
+      UserEvent::MouseMoved(x, y) => {
+        // Hovered are the topmost element under the cursor and the ones enclosing it, not what it covers
+        for element in layout.iter_mut() {
+          element.layout_mut().variants.interaction.hovered = false;
+        }
+        layout.visit_hit(x, y, &mut |element| {
+          element.layout_mut().variants.interaction.hovered = true;
+          false
+        });
+      }
+      UserEvent::MouseLeft => {
+        for element in layout.iter_mut() {
+          let interaction = &mut element.layout_mut().variants.interaction;
+          interaction.hovered = false;
+          interaction.pressed = false;
+        }
+      }
+      UserEvent::Press(x, y) => {
+        let target = Self::hit(&**layout, x, y);
+
+        for (i, element) in layout.iter_mut().enumerate() {
+          let interaction = &mut element.layout_mut().variants.interaction;
+          interaction.pressed = false;
+          interaction.focused = Some(i) == target;
+        }
+        layout.visit_hit(x, y, &mut |element| {
+          element.layout_mut().variants.interaction.pressed = true;
+          false
+        });
+        self.press_target = target;
+      }
+      UserEvent::Release(x, y) => {
+        for element in layout.iter_mut() {
+          element.layout_mut().variants.interaction.pressed = false;
+        }
+
+        // Releasing over the element the press started on is a click
+        let target = Self::hit(&**layout, x, y);
+        if target.is_some() && self.press_target.take() == target {
+          self.deliver(UserEvent::Click(x, y));
+        }
+      }
+      _ => {}
+    }
+
+    self.scheduler.request();
   }
 
-  fn create_surface(
-    &mut self,
-    width : u32,
-    height: u32,
-  ) -> Surface {
-    
-      let num_samples = self.state.as_ref().unwrap().num_samples;
-      let stencil_bits = self.state.as_ref().unwrap().stencil_size;
-      let fb_info = self.state.as_ref().unwrap().fb_info;
-
-      let backend_render_target = 
-          backend_render_targets::make_gl((width as i32, height as i32), num_samples, stencil_bits, fb_info);
-
-      gpu::surfaces::wrap_backend_render_target(
-          &mut self.state.as_mut().unwrap().gr_context,
-          &backend_render_target,
-          SurfaceOrigin::BottomLeft,
-          ColorType::RGBA8888,
-          None,
-          None,
-      )
-      .expect("Could not create skia surface")
+  /* Topmost enabled element under the point, it comes last in paint order */
+  fn hit(layout : &dyn LayoutElement, x : f32, y : f32) -> Option<usize> {
+    layout.iter()
+      .enumerate()
+      .filter(|(_, e)| e.layout().contains(x, y) && !e.layout().variants.interaction.disabled)
+      .last()
+      .map(|(i, _)| i)
   }
 
-  pub fn update(&mut self) {
-    
-    let state = self.state.as_mut().unwrap();
+  /* Offers a positional event to the topmost element under it and then the ones enclosing it, until one consumes it */
+  fn deliver(&mut self, event : UserEvent) -> bool {
+    let Some(layout) = &mut self.layout else { return false };
+    layout.deliver(event)
+  }
 
+  /* Resizes the drawable of the backend to the new physical size of the window */
+  fn resize(&mut self, size : PhysicalSize<u32>) {
+    let Some(state) = self.state.as_mut() else { return };
 
-    state.frame += 1;
-    let canvas = state.surface.canvas();
-    canvas.clear(Color::WHITE);
-    let frame : (u32, u32)= state.window.inner_size().into();
+    let (width, height): (u32, u32) = size.into();
+    state.backend.resize(width, height);
+    self.damage.reset();
+    self.scheduler.request();
+  }
+
+  pub fn update(&mut self) {
+    let state = self.state.as_mut().unwrap();
+    let start = Instant::now();
 
-    let mut rng = StdRng::seed_from_u64(10);
+    let clear = self.active_theme.map_or(Color::WHITE, |i| self.themes[i].clear_color);
+    let scale_factor = state.window.scale_factor() as f32;
 
+    let mut area = None;
     if let Some(layout) = &mut self.layout {
-        
-        if frame.0 != 0 && frame.1 != 0 {
-            layout.calculate(frame.0, frame.1, 10);
-
-            for child in layout.iter()  {
-                let paint  = Paint::new(Color4f::new(rng.random_range(0..255) as f32 / 255.0, rng.random_range(0..255) as f32 / 255.0, rng.random_range(0..255) as f32 / 255.0, 1.0), None);
-                let layout = child.layout();
-                canvas.draw_rect(
-                  skia_safe::Rect::new(
-                    layout.computed.inner_pos.0 as f32,
-                    layout.computed.inner_pos.1 as f32,
-                    (layout.computed.inner_pos.0 + layout.computed.inner_dim.0) as f32,
-                    (layout.computed.inner_pos.1 + layout.computed.inner_dim.1) as f32,
-                  ),
-                  &paint
-                );
-            };
+      // Sources set at runtime are loaded before layout, a source that fails to load stays empty
+      for element in layout.iter_mut().filter(|element| element.images_stale()) {
+        let _ = element.load_images(&mut self.images);
+      }
+
+      if backend::layout(&mut state.backend, layout.as_mut(), scale_factor, self.unit_size, &self.text) {
+        // Only what changed is repainted, plus what changed since the back buffer was last drawn
+        let mut damage = self.damage.collect(layout.as_mut());
+        if self.overlay {
+          damage = damage.union(Damage::Area(FrameStats::overlay_rect(OVERLAY_ORIGIN)));
         }
+        area = Some(self.damage.repaint(damage, state.backend.buffer_age()));
+      }
     }
+    let laid_out = Instant::now();
 
+    match (&self.layout, area) {
+      (Some(layout), Some(area)) => paint(&mut state.backend, layout.as_ref(), clear, scale_factor, self.unit_size, area),
+      _ => state.backend.surface().canvas().clear(clear),
+    }
+    if self.overlay {
+      let canvas = state.backend.surface().canvas();
+      canvas.save();
+      canvas.scale((scale_factor, scale_factor));
+      self.stats.draw_overlay(canvas, OVERLAY_ORIGIN, &self.text.collection);
+      canvas.restore();
+    }
+    let painted = Instant::now();
+    if let Some(layout) = &self.layout {
+      self.stats.set_cache(layout.cache_stats());
+    }
+
+    state.backend.present();
+    self.stats.record(start, laid_out, painted, Instant::now());
 
-    state.gr_context.flush_and_submit();
-    state.gl_surface
-          .swap_buffers(&state.gl_context)
-          .unwrap();   
-      
+    let animating = self.layout.as_ref().is_some_and(|layout| layout.animating());
+    self.scheduler.presented(animating);
   }
- 
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::gui::{canvas::CanvasElement, container::Container, layout::{Size, Transform}};
+
+  #[test]
+  fn test_unit_size() {
+    assert_eq!(unit_size(16.3, 1.0), 16);
+    assert_eq!(unit_size(16.3, 2.0), 33);
+    assert_eq!(unit_size(16.3, 1.1 * 1.5), 27);
+    assert_eq!(unit_size(0.0, 1.0), 1);
+  }
+
+  #[test]
+  fn test_to_logical() {
+    // The same spot of a window on a 1x and on a 2x monitor
+    assert_eq!(to_logical((300.0, 120.0), 1.0), (300.0, 120.0));
+    assert_eq!(to_logical((600.0, 240.0), 2.0), (300.0, 120.0));
+    assert_eq!(to_logical((450.0, 180.0), 1.5), (300.0, 120.0));
+  }
+
+  #[test]
+  fn test_hit_topmost() {
+    let clicked = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let card = |name: &'static str| {
+      let clicked = clicked.clone();
+      CanvasElement::new(|_, _| {})
+        .on_event(move |_, _| { clicked.borrow_mut().push(name); true })
+        .width(Size::Unit(4.0))
+        .height(Size::Unit(4.0))
+    };
+
+    // The second card is moved onto the first one and painted over it
+    let mut root = Container::new("Root").horizontal(vec![
+      card("first").boxed(),
+      card("second").transform(Transform::new().translate(-4.0, 0.0)).boxed(),
+    ]);
+    root.calculate(100, 100, 10, &TextContext::default());
+    let root: &mut dyn LayoutElement = &mut root;
+
+    assert_eq!(Application::hit(root, 20.0, 20.0), Some(2));
+    assert!(root.deliver(UserEvent::Click(20.0, 20.0)));
+    assert_eq!(*clicked.borrow(), ["second"]);
+
+    // Only the visible card and the root enclosing it are hovered
+    let mut hovered = Vec::new();
+    root.visit_hit(20.0, 20.0, &mut |element| {
+      hovered.push(element.name().map(str::to_string));
+      false
+    });
+    assert_eq!(hovered.len(), 2);
+    assert_eq!(hovered.last(), Some(&Some("Root".to_string())));
+  }
+}
@@ -0,0 +1,310 @@
+use std::{num::NonZeroU32, time::Instant};
+
+use gl_rs::{types::GLint, GetIntegerv, FRAMEBUFFER_BINDING};
+use glutin::{
+  config::ConfigTemplateBuilder,
+  context::{ContextApi, ContextAttributesBuilder, PossiblyCurrentContext, Version},
+  display::GetGlDisplay,
+  prelude::*,
+  surface::{Surface as GlutinSurface, SurfaceAttributesBuilder, WindowSurface},
+};
+use skia_safe::{gpu::{self, backend_render_targets, gl::FramebufferInfo, DirectContext, SurfaceOrigin}, surfaces, Color, ColorType, Image, Pixmap, Rect, Surface};
+use winit::{event_loop::ActiveEventLoop, raw_window_handle::HasWindowHandle, window::{Window, WindowAttributes}};
+
+use crate::{damage::Damage, gui::{image::{ImageCache, ImageError}, layout::LayoutElement, text::TextContext}};
+
+
+/* Target a frame is painted into */
+pub trait Backend {
+  /* Surface the next frame is painted on, in physical pixels */
+  fn surface(&mut self) -> &mut Surface;
+
+  /* Adapts the surface to a new size in physical pixels */
+  fn resize(&mut self, width: u32, height: u32);
+
+  /* Finishes the frame, e.g. flushes the GPU work and swaps the buffers */
+  fn present(&mut self) {}
+
+  /* Frames since the content of the surface was drawn, 0 if it is unknown and has to be painted completely */
+  fn buffer_age(&self) -> u32 { 0 }
+}
+
+
+/* Logical size of a physical surface, rounded up so the layout covers every physical pixel */
+pub fn logical_size(physical : (u32, u32), scale_factor : f64) -> (u32, u32) {
+  (
+    (physical.0 as f64 / scale_factor).ceil() as u32,
+    (physical.1 as f64 / scale_factor).ceil() as u32,
+  )
+}
+
+
+/* Loads the images of the tree into a new cache and hands it the unit size, for renders without an Application */
+pub fn prepare(root : &mut dyn LayoutElement, unit_size : u32) -> Result<(), ImageError> {
+  let mut images = ImageCache::new();
+  for element in root.iter_mut() {
+    element.load_images(&mut images)?;
+    element.update_unit_size(unit_size);
+  }
+  Ok(())
+}
+
+
+/* Lays out the tree at the logical size of the backend's surface and advances its transitions, false if the surface is empty */
+pub fn layout(backend : &mut dyn Backend, root : &mut dyn LayoutElement, scale_factor : f32, unit_size : u32, text : &TextContext) -> bool {
+  let surface = backend.surface();
+  let frame = logical_size((surface.width() as u32, surface.height() as u32), scale_factor as f64);
+  if frame.0 == 0 || frame.1 == 0 { return false }
+
+  root.calculate(frame.0, frame.1, unit_size, text);
+  root.animate(Instant::now());
+  true
+}
+
+/*
+Paints the laid out tree onto the surface of the backend, only inside the damaged area if there is one.
+Layout is in logical pixels, the canvas maps them onto the physical pixels of the surface.
+*/
+pub fn paint(backend : &mut dyn Backend, root : &dyn LayoutElement, clear : Color, scale_factor : f32, unit_size : u32, damage : Damage) {
+  let canvas = backend.surface().canvas();
+  canvas.save();
+
+  if let Damage::Area(area) = damage {
+    if area.is_empty() {
+      canvas.restore();
+      return;
+    }
+    // Rounded out to whole physical pixels, antialiased clip edges would blend with the stale content
+    let physical = Rect::new(area.left * scale_factor, area.top * scale_factor, area.right * scale_factor, area.bottom * scale_factor);
+    canvas.clip_irect(physical.round_out(), None);
+  }
+
+  canvas.clear(clear);
+  canvas.scale((scale_factor, scale_factor));
+  root.display_list(unit_size).replay(canvas);
+  canvas.restore();
+}
+
+/* Lays out and paints the whole tree */
+pub fn render(backend : &mut dyn Backend, root : &mut dyn LayoutElement, clear : Color, scale_factor : f32, unit_size : u32, text : &TextContext) {
+  if layout(backend, root, scale_factor, unit_size, text) {
+    paint(backend, root, clear, scale_factor, unit_size, Damage::Full);
+  } else {
+    backend.surface().canvas().clear(clear);
+  }
+}
+
+
+/* Paints on the CPU into memory, needs neither a window nor a GPU */
+pub struct RasterBackend {
+  surface : Surface,
+}
+
+
+impl RasterBackend {
+
+  pub fn new(width : u32, height : u32) -> Option<Self> {
+    Some(RasterBackend { surface: raster_surface(width, height)? })
+  }
+
+  /* Pixels of the last frame */
+  pub fn pixmap(&mut self) -> Pixmap<'_> {
+    self.surface.peek_pixels().expect("raster surfaces expose their pixels")
+  }
+
+  /* Copy of the last frame */
+  pub fn snapshot(&mut self) -> Image {
+    self.surface.image_snapshot()
+  }
+}
+
+impl Backend for RasterBackend {
+  fn surface(&mut self) -> &mut Surface {
+    &mut self.surface
+  }
+
+  fn resize(&mut self, width: u32, height: u32) {
+    if let Some(surface) = raster_surface(width, height) {
+      self.surface = surface;
+    }
+  }
+
+  // The memory keeps the last frame
+  fn buffer_age(&self) -> u32 { 1 }
+}
+
+fn raster_surface(width : u32, height : u32) -> Option<Surface> {
+  surfaces::raster_n32_premul((width.max(1) as i32, height.max(1) as i32))
+}
+
+
+/* Paints with OpenGL into the window it was created with */
+pub struct GlBackend {
+  surface: Surface,
+  gl_surface: GlutinSurface<WindowSurface>,
+  gr_context: DirectContext,
+  gl_context: PossiblyCurrentContext,
+
+  fb_info: FramebufferInfo,
+  num_samples: usize,
+  stencil_size: usize,
+}
+
+
+impl GlBackend {
+
+  /* Opens a window with the attributes and an OpenGL context on it */
+  pub fn new(event_loop : &ActiveEventLoop, attributes : WindowAttributes) -> (Window, Self) {
+    let template = ConfigTemplateBuilder::new();
+    let (window, gl_config)  = glutin_winit::DisplayBuilder::new()
+        .with_window_attributes(Some(attributes))
+        .build(event_loop, template, |mut configs| configs.next().unwrap())
+        .unwrap();
+
+    let window = window.unwrap();
+    let window_handle = window.window_handle().unwrap().as_raw();
+
+    let gl_display = gl_config.display();
+
+    let context_attributes = ContextAttributesBuilder::new()
+      .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
+      .build(Some(window_handle));
+
+    let not_current_gl_context = unsafe {
+      gl_display.create_context(&gl_config, &context_attributes).unwrap()
+    };
+
+    let attrs = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+        window_handle,
+        NonZeroU32::new(window.inner_size().width).unwrap(),
+        NonZeroU32::new(window.inner_size().height).unwrap(),
+    );
+
+    let gl_surface = unsafe { gl_display.create_window_surface(&gl_config, &attrs).unwrap() };
+
+    let gl_context = not_current_gl_context.make_current(&gl_surface).unwrap();
+
+    gl_rs::load_with(|s| {
+        let cstr = std::ffi::CString::new(s).unwrap();
+        gl_display.get_proc_address(&cstr)
+    });
+
+    let interface = gpu::gl::Interface::new_load_with(|s| {
+        let cstr = std::ffi::CString::new(s).unwrap();
+        gl_display.get_proc_address(&cstr) as _
+    }).unwrap();
+
+    let mut gr_context  = gpu::direct_contexts::make_gl(interface, None).unwrap();
+
+    let fb_info = {
+      let mut fboid: GLint = 0;
+      unsafe { GetIntegerv(FRAMEBUFFER_BINDING, &mut fboid) };
+
+      FramebufferInfo {
+          fboid: fboid.try_into().unwrap(),
+          format: skia_safe::gpu::gl::Format::RGBA8.into(),
+          ..Default::default()
+      }
+    };
+
+    let num_samples = gl_config.num_samples() as usize;
+    let stencil_size = gl_config.stencil_size() as usize;
+    let size = window.inner_size();
+    let surface = wrap_framebuffer(&mut gr_context, (size.width, size.height), num_samples, stencil_size, fb_info);
+
+    let backend = GlBackend { surface, gl_surface, gr_context, gl_context, fb_info, num_samples, stencil_size };
+    (window, backend)
+  }
+}
+
+/* Skia surface drawing into the framebuffer of the window */
+fn wrap_framebuffer(gr_context : &mut DirectContext, size : (u32, u32), num_samples : usize, stencil_size : usize, fb_info : FramebufferInfo) -> Surface {
+  let backend_render_target =
+      backend_render_targets::make_gl((size.0 as i32, size.1 as i32), num_samples, stencil_size, fb_info);
+
+  gpu::surfaces::wrap_backend_render_target(
+      gr_context,
+      &backend_render_target,
+      SurfaceOrigin::BottomLeft,
+      ColorType::RGBA8888,
+      None,
+      None,
+  )
+  .expect("Could not create skia surface")
+}
+
+impl Backend for GlBackend {
+  fn surface(&mut self) -> &mut Surface {
+    &mut self.surface
+  }
+
+  fn resize(&mut self, width: u32, height: u32) {
+    self.surface = wrap_framebuffer(&mut self.gr_context, (width, height), self.num_samples, self.stencil_size, self.fb_info);
+
+    self.gl_surface.resize(
+        &self.gl_context,
+        NonZeroU32::new(width.max(1)).unwrap(),
+        NonZeroU32::new(height.max(1)).unwrap(),
+    );
+  }
+
+  fn present(&mut self) {
+    self.gr_context.flush_and_submit();
+    self.gl_surface.swap_buffers(&self.gl_context).unwrap();
+  }
+
+  fn buffer_age(&self) -> u32 {
+    self.gl_surface.buffer_age()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::gui::{container::Container, layout::Size, style::Background};
+
+  #[test]
+  fn test_logical_size() {
+    assert_eq!(logical_size((1280, 780), 1.0), (1280, 780));
+    assert_eq!(logical_size((2560, 1560), 2.0), (1280, 780));
+    assert_eq!(logical_size((1921, 1081), 1.5), (1281, 721));
+    assert_eq!(logical_size((1280, 780), 0.5), (2560, 1560));
+  }
+
+  #[test]
+  fn test_raster_render() {
+    let mut root = Container::new("Root")
+      .width(Size::Unit(4.0))
+      .height(Size::Unit(4.0))
+      .background(Color::RED);
+
+    // 4 units of 2 logical pixels at a scale of 2 cover 16 physical pixels
+    let mut backend = RasterBackend::new(20, 20).unwrap();
+    render(&mut backend, &mut root, Color::BLUE, 2.0, 2, &TextContext::default());
+
+    let pixels = backend.pixmap();
+    assert_eq!(pixels.get_color((15, 15)), Color::RED);
+    assert_eq!(pixels.get_color((17, 17)), Color::BLUE);
+  }
+
+  #[test]
+  fn test_partial_paint() {
+    let mut root = Container::new("Root")
+      .width(Size::Max)
+      .height(Size::Max)
+      .background(Color::RED);
+
+    let mut backend = RasterBackend::new(20, 20).unwrap();
+    render(&mut backend, &mut root, Color::WHITE, 1.0, 2, &TextContext::default());
+
+    // Outside the damaged area the previous frame stays
+    root.layout_mut().style.background = Background::Color(Color::GREEN);
+    assert!(layout(&mut backend, &mut root, 1.0, 2));
+    paint(&mut backend, &root, Color::WHITE, 1.0, 2, Damage::Area(Rect::from_xywh(0.0, 0.0, 10.0, 20.0)));
+
+    let pixels = backend.pixmap();
+    assert_eq!(pixels.get_color((5, 5)), Color::GREEN);
+    assert_eq!(pixels.get_color((15, 5)), Color::RED);
+  }
+}
@@ -0,0 +1,32 @@
+use redwood::{demo, gui::{layout::LayoutElement, theme::Theme}, screenshot::Screenshot};
+
+
+const USAGE: &str = "usage: screenshot <output.png> [width] [height] [scale factor] [unit size] [light|dark|high-contrast]";
+
+
+/* Renders the demo layout off-screen and writes it as a PNG file */
+fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(output) = args.first() else { return Err(USAGE.into()) };
+
+    let arg = |i: usize, default: &str| args.get(i).map(String::as_str).unwrap_or(default).to_string();
+    let number = |i: usize, default: &str| arg(i, default).parse::<f32>().map_err(|_| USAGE.to_string());
+
+    let fonts = demo::fonts().map_err(|e| e.to_string())?;
+
+    let screenshot = Screenshot::new(number(1, "1280")? as u32, number(2, "780")? as u32)
+        .scale_factor(number(3, "1")?)
+        .unit_size(number(4, "16")? as u32)
+        .fonts(&fonts);
+
+    let theme = [Theme::light(), Theme::dark(), Theme::high_contrast()]
+        .into_iter()
+        .find(|theme| theme.name == arg(5, "light"))
+        .ok_or(USAGE)?;
+
+    let mut layout = demo::layout();
+    fonts.check(&layout).map_err(|e| e.to_string())?;
+    theme.apply(&mut layout as &mut dyn LayoutElement);
+
+    screenshot.clear(theme.clear_color).save(&mut layout, output).map_err(|e| e.to_string())
+}
@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+
+use skia_safe::Rect;
+
+use crate::gui::{layout::LayoutElement, style::Style};
+
+
+/* Frames of damage kept for buffers that are several frames old */
+const MAX_BUFFER_AGE: usize = 4;
+
+
+/* Area that changed since the previous frame, in logical pixels */
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Damage {
+  Area(Rect), // empty if nothing changed
+  Full,
+}
+
+
+impl Damage {
+
+  pub fn union(self, other : Damage) -> Damage {
+    match (self, other) {
+      (Damage::Area(mut a), Damage::Area(b)) => {
+        if a.is_empty() { return Damage::Area(b) }
+        if !b.is_empty() { a.join(b); }
+        Damage::Area(a)
+      }
+      _ => Damage::Full,
+    }
+  }
+}
+
+
+/*
+Finds the parts of the window to repaint by comparing every element's frame and style with the previous frame.
+Changes the comparison can't see (new text, a new image) are reported by the elements through Layout::damage.
+*/
+#[derive(Default)]
+pub struct DamageTracker {
+  previous : Vec<(Rect, Style)>,  // frame and style of every element in the last frame, depth first
+  history : VecDeque<Damage>,     // damage of the last frames, newest first
+}
+
+
+impl DamageTracker {
+
+  pub fn new() -> Self { Default::default() }
+
+  /* Forgets the previous frames, the next frame is painted completely, e.g. after a resize */
+  pub fn reset(&mut self) {
+    self.previous.clear();
+    self.history.clear();
+  }
+
+  /* Damage of the laid out and animated tree since the last collection */
+  pub fn collect(&mut self, root : &mut dyn LayoutElement) -> Damage {
+    let mut damage = Damage::Area(Rect::new_empty());
+    let mut current = Vec::with_capacity(self.previous.len());
+
+    for (i, element) in root.iter_mut().enumerate() {
+      let layout = element.layout_mut();
+      let (rect, style) = (layout.window_rect(), layout.current_style());
+
+      let changed = match self.previous.get(i) {
+        Some((previous_rect, previous_style)) => {
+          let changed = layout.take_damage() || *previous_rect != rect || *previous_style != style;
+          if changed { damage = damage.union(Damage::Area(*previous_rect)); }
+          changed
+        }
+        None => { layout.take_damage(); true }
+      };
+      if changed { damage = damage.union(Damage::Area(rect)); }
+
+      current.push((rect, style));
+    }
+
+    // Added or removed elements shift the depth first order, the comparison doesn't hold anymore
+    if current.len() != self.previous.len() {
+      damage = Damage::Full;
+    }
+
+    self.previous = current;
+    damage
+  }
+
+  /*
+  Area to repaint for the frame's damage on a back buffer of the given age: the buffer still holds the frame
+  drawn that many frames ago, so the damage of every frame since then is repainted as well.
+  An age of 0 means the content of the buffer is unknown.
+  */
+  pub fn repaint(&mut self, damage : Damage, buffer_age : u32) -> Damage {
+    self.history.push_front(damage);
+    self.history.truncate(MAX_BUFFER_AGE);
+
+    let age = buffer_age as usize;
+    if age == 0 || age > self.history.len() {
+      return Damage::Full;
+    }
+    self.history.iter().take(age).fold(Damage::Area(Rect::new_empty()), |area, damage| area.union(*damage))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use skia_safe::Color;
+
+  use super::*;
+  use crate::{gui::{container::Container, layout::Size}, testing::{lay_out, layout_of}};
+
+  #[test]
+  fn test_collect() {
+    let mut root = Container::new("Root")
+      .width(Size::Max)
+      .height(Size::Max)
+      .horizontal(vec![
+        Container::new("A").width(Size::Unit(2.0)).height(Size::Unit(2.0)).background(Color::RED).boxed(),
+        Container::new("B").width(Size::Unit(2.0)).height(Size::Unit(2.0)).background(Color::BLUE).boxed(),
+      ]);
+    let root: &mut dyn LayoutElement = &mut root;
+    let mut tracker = DamageTracker::new();
+
+    let frame = |root: &mut dyn LayoutElement, tracker: &mut DamageTracker| {
+      lay_out(root);
+      tracker.collect(root)
+    };
+
+    assert_eq!(frame(root, &mut tracker), Damage::Full);
+    assert_eq!(frame(root, &mut tracker), Damage::Area(Rect::new_empty()));
+
+    // Only the element whose style changed is repainted
+    layout_of(root, "B").style.opacity = 0.5;
+    assert_eq!(frame(root, &mut tracker), Damage::Area(Rect::from_xywh(20.0, 0.0, 20.0, 20.0)));
+
+    layout_of(root, "A").damage();
+    assert_eq!(frame(root, &mut tracker), Damage::Area(Rect::from_xywh(0.0, 0.0, 20.0, 20.0)));
+    assert_eq!(frame(root, &mut tracker), Damage::Area(Rect::new_empty()));
+  }
+
+  #[test]
+  fn test_buffer_age() {
+    let mut tracker = DamageTracker::new();
+    let a = Damage::Area(Rect::from_xywh(0.0, 0.0, 10.0, 10.0));
+    let b = Damage::Area(Rect::from_xywh(20.0, 0.0, 10.0, 10.0));
+
+    assert_eq!(tracker.repaint(a, 1), a);
+    assert_eq!(tracker.repaint(b, 0), Damage::Full);
+    assert_eq!(tracker.repaint(b, 1), b);
+    assert_eq!(tracker.repaint(Damage::Area(Rect::new_empty()), 3), Damage::Area(Rect::from_xywh(20.0, 0.0, 10.0, 10.0)));
+    assert_eq!(tracker.repaint(a, 4), Damage::Area(Rect::from_xywh(0.0, 0.0, 30.0, 10.0)));
+    assert_eq!(tracker.repaint(a, 9), Damage::Full);
+  }
+}
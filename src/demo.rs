@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use skia_safe::{Color, Paint, PaintStyle, Path};
+
+use crate::gui::{canvas::CanvasElement, container::Container, fonts::{FontError, FontManager}, image::{Fit, ImageElement, Sampling}, paragraph::ParagraphElement, rich_text::{RichText, Span}, text::TextElement, layout::{Alignment, Axis, LayoutElement, Size}, theme::Properties};
+#[cfg(feature = "svg")]
+use crate::gui::svg::SvgElement;
+
+
+/* Showcase of the elements, opened by the redwood binary and rendered by the screenshot binary */
+pub fn layout() -> Container {
+    Container::new("Main Page")
+        .class("surface")
+        .horizontal(vec![
+            Container::new("Left Part")
+                .width(Size::Unit(20.0))
+                .height(Size::Max)
+                .margin(0.5)
+                .padding(1.0)
+                .class("panel")
+                .vertical(vec![
+                    TextElement::new("Redwood")
+                        .font_family("Cantarell")
+                        .font_size(24.0)
+                        .bold()
+                        .width(Size::Max)
+                        .text_align(Alignment::Center, Alignment::Center)
+                        .margin(0.5)
+                        .boxed(),
+                    TextElement::new("שלום · مرحبا · नमस्ते · こんにちは 👋")
+                        .font_fallback(["DejaVu Sans", "Noto Sans", "Noto Sans CJK JP", "Noto Color Emoji"])
+                        .width(Size::Max)
+                        .text_align(Alignment::Center, Alignment::Center)
+                        .margin(0.5)
+                        .boxed(),
+                    ParagraphElement::new("Redwood lays out elements in font units and paints them with skia. Long descriptions like this one wrap to the width of their container.")
+                        .width(Size::Max)
+                        .line_height(1.3)
+                        .max_lines(3)
+                        .margin(0.5)
+                        .boxed(),
+                    RichText::new([
+                            Span::new("Status: ").bold(),
+                            Span::new("connected").color(Color::from_rgb(163, 190, 140)),
+                            Span::new("  "),
+                            Span::new("details").color(Color::from_rgb(94, 129, 172)).link(|_| {}),
+                        ])
+                        .width(Size::Max)
+                        .margin(0.5)
+                        .boxed(),
+                    Container::new("Left Part")
+                        .width(Size::Max)
+                        .height(Size::Unit(20.0))
+                        .gradient(Color::from_rgb(136, 192, 208), Color::from_rgb(94, 129, 172), Axis::Vertical)
+                        .rounded_all(1.0, 1.0, 0.0, 0.0)
+                        .boxed(),
+                    Container::new("Left Part")
+                        .width(Size::Max)
+                        .height(Size::Unit(20.0))
+                        .margin(0.7)
+                        .class("card")
+                        .boxed(),
+                ])
+
+                .align(Alignment::Even)
+                .boxed(),
+            Container::new("Right Part")
+                .width(Size::Max)
+                .height(Size::Max)
+                .horizontal(vec![
+                    Container::new("Left Part")
+                    .width(Size::Same)
+                    .height(Size::Unit(20.0))
+                    .margin(0.7)
+                    .class("button")
+                    .transition(Duration::from_millis(120))
+                    .boxed(),
+                    Container::new("Left Part")
+                    .width(Size::Same)
+                    .height(Size::Unit(20.0))
+                    .margin(0.7)
+                    .background(Color::from_rgb(163, 190, 140))
+                    .rounded(0.5)
+                    .boxed(),
+                    Container::new("Left Part")
+                    .width(Size::Same)
+                    .height(Size::Unit(40.0))
+                    .margin(0.7)
+                    .background(Color::from_rgb(191, 97, 106))
+                    .opacity(0.8)
+                    .rounded(0.5)
+                    .hovered(Properties::new().opacity(1.0).rounded(2.0))
+                    .pressed(Properties::new().background(Color::from_rgb(208, 135, 112)))
+                    .transition(Duration::from_millis(200))
+                    .boxed(),
+                    Container::new("Left Part")
+                    .width(Size::Same)
+                    .height(Size::Unit(20.0))
+                    .margin(0.7)
+                    .background(Color::from_rgb(163, 190, 140))
+                    .rounded(0.5)
+                    .boxed(),    
+                ])
+                .align(Alignment::Even)
+                .boxed(),
+            Container::new("Left Part")
+                .width(Size::Unit(20.0))
+                .height(Size::Max)
+                .class("panel")
+                .vertical(vec![
+                    ImageElement::new(&include_bytes!("../assets/images/swatch.png")[..])
+                        .fit(Fit::Contain)
+                        .sampling(Sampling::Nearest)
+                        .width(Size::Max)
+                        .height(Size::Unit(6.0))
+                        .margin(0.5)
+                        .boxed(),
+                    #[cfg(feature = "svg")]
+                    SvgElement::new(&include_bytes!("../assets/icons/star.svg")[..])
+                        .tint(Color::from_rgb(235, 203, 139))
+                        .width(Size::Content)
+                        .height(Size::Unit(3.0))
+                        .margin(0.5)
+                        .boxed(),
+                    CanvasElement::new(|canvas, (width, height)| {
+                            let mut paint = Paint::default();
+                            paint.set_anti_alias(true);
+                            paint.set_style(PaintStyle::Stroke);
+                            paint.set_stroke_width(2.0);
+                            paint.set_color(Color::from_rgb(94, 129, 172));
+
+                            let mut path = Path::new();
+                            for x in 0..=width as u32 {
+                                let y = height / 2.0 * (1.0 - (x as f32 / width * std::f32::consts::TAU * 2.0).sin() * 0.8);
+                                if x == 0 { path.move_to((0.0, y)); } else { path.line_to((x as f32, y)); }
+                            }
+                            canvas.draw_path(&path, &paint);
+                        })
+                        .width(Size::Max)
+                        .height(Size::Unit(6.0))
+                        .margin(0.5)
+                        .boxed(),
+                ])
+                .boxed()
+        ])
+}
+
+/* Font families the demo layout uses besides the system fonts */
+pub fn fonts() -> Result<FontManager, FontError> {
+    let mut fonts = FontManager::new();
+    fonts.register_bytes(include_bytes!("../assets/fonts/Cantarell-Regular.ttf"))?;
+    fonts.register_bytes(include_bytes!("../assets/fonts/DejaVuSans.ttf"))?;
+    Ok(fonts)
+}
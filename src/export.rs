@@ -0,0 +1,386 @@
+use std::{collections::HashSet, fmt, path::Path, time::Instant};
+
+use skia_safe::{pdf, svg, Color, Rect};
+
+use crate::{backend::prepare, gui::{fonts::FontManager, image::ImageError, layout::LayoutElement, style::rounded_rect, text::TextContext}, objects::{DisplayItem, DisplayList}, screenshot::{write_file, WriteError}};
+
+
+#[derive(Debug)]
+pub enum ExportError {
+  Image(ImageError),
+  Write(WriteError),
+}
+
+
+impl fmt::Display for ExportError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ExportError::Image(error) => error.fmt(f),
+      ExportError::Write(error) => error.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for ExportError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ExportError::Image(error) => Some(error),
+      ExportError::Write(error) => Some(error),
+    }
+  }
+}
+
+impl From<ImageError> for ExportError {
+  fn from(error: ImageError) -> Self { ExportError::Image(error) }
+}
+
+impl From<WriteError> for ExportError {
+  fn from(error: WriteError) -> Self { ExportError::Write(error) }
+}
+
+
+/* Page sizes in points (1/72 inch) */
+pub const A4: (f32, f32) = (595.0, 842.0);
+pub const LETTER: (f32, f32) = (612.0, 792.0);
+
+
+/* Number of pages needed for content of the given height */
+fn page_count(extent : f32, page_height : f32) -> usize {
+  if page_height <= 0.0 { return 1 }
+  ((extent / page_height).ceil() as usize).max(1)
+}
+
+
+/*
+Export of a layout tree into a PDF document, text and shapes stay vectors.
+The tree is laid out at the width of the page content, whatever extends below the first page
+continues on the next pages.
+*/
+#[derive(Clone, Debug)]
+pub struct PdfExport {
+  pub page_size : (f32, f32), // in points
+  pub margin : [f32; 4],      // left, right, top, bottom in points
+  pub scale_factor : f32,     // points per logical pixel
+  pub unit_size : u32,
+  pub title : String,
+  pub text : TextContext,     // system fonts unless a font manager is given
+}
+
+
+impl PdfExport {
+
+  pub fn new(page_size : (f32, f32)) -> Self {
+    // 0.75 maps the 96 logical pixels per inch of a screen onto the 72 points of an inch on paper
+    PdfExport { page_size, margin: [36.0; 4], scale_factor: 0.75, unit_size: 16, title: String::new(), text: TextContext::default() }
+  }
+
+  pub fn margin(mut self, margin : f32) -> Self {
+    self.margin = [margin; 4];
+    self
+  }
+
+  pub fn margins(mut self, left : f32, right : f32, top : f32, bottom : f32) -> Self {
+    self.margin = [left, right, top, bottom];
+    self
+  }
+
+  pub fn scale_factor(mut self, scale_factor : f32) -> Self {
+    self.scale_factor = scale_factor;
+    self
+  }
+
+  pub fn unit_size(mut self, unit_size : u32) -> Self {
+    self.unit_size = unit_size;
+    self
+  }
+
+  pub fn title<T: Into<String>>(mut self, title : T) -> Self {
+    self.title = title.into();
+    self
+  }
+
+  /* Shapes text with the families registered in the font manager */
+  pub fn fonts(mut self, fonts : &FontManager) -> Self {
+    self.text = TextContext::new(fonts.collection());
+    self
+  }
+
+  /* Area of a page inside the margins, in points */
+  pub fn content_rect(&self) -> Rect {
+    let [left, right, top, bottom] = self.margin;
+    Rect::new(left, top, self.page_size.0 - right, self.page_size.1 - bottom)
+  }
+
+  /* Lays out the tree (or a subtree) and paints it page by page into a PDF document */
+  pub fn pdf(&self, root : &mut dyn LayoutElement) -> Result<Vec<u8>, ImageError> {
+    prepare(root, self.unit_size)?;
+
+    let content = self.content_rect();
+    let page_height = content.height() / self.scale_factor;
+    root.calculate((content.width() / self.scale_factor) as u32, page_height as u32, self.unit_size, &self.text);
+    root.animate(Instant::now());
+
+    // Elements sized in units can extend past the frame they were laid out in
+    let extent = root.iter()
+      .map(|e| e.layout().computed.outer_pos.1 + e.layout().computed.outer_dim.1)
+      .max()
+      .unwrap_or(0);
+
+    let metadata = pdf::Metadata { title: self.title.clone(), creator: "Redwood".into(), ..Default::default() };
+    let mut data = Vec::new();
+    let mut document = pdf::new_document(&mut data, Some(&metadata));
+
+    for page in 0..page_count(extent as f32, page_height) {
+      let mut on_page = document.begin_page(self.page_size, None);
+      let canvas = on_page.canvas();
+
+      canvas.clip_rect(content, None, true);
+      canvas.translate((content.left, content.top));
+      canvas.scale((self.scale_factor, self.scale_factor));
+      canvas.translate((0.0, -page_height * page as f32));
+      root.paint_tree(canvas, self.unit_size);
+
+      document = on_page.end_page();
+    }
+    document.close();
+
+    Ok(data)
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, root : &mut dyn LayoutElement, path : P) -> Result<(), ExportError> {
+    let path = path.as_ref();
+    let pdf = self.pdf(root)?;
+    Ok(write_file(path, pdf)?)
+  }
+}
+
+
+/* Escapes text for an XML attribute value */
+fn escape(text : &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/* Body of a document written by skia's SVG canvas, with its ids prefixed so fragments can be combined */
+fn svg_body(document : &str, prefix : &str) -> String {
+  let start = document.find("<svg").and_then(|i| document[i..].find('>').map(|j| i + j + 1)).unwrap_or(0);
+  let end = document.rfind("</svg>").unwrap_or(document.len()).max(start);
+
+  document[start..end]
+    .trim()
+    .replace("id=\"", &format!("id=\"{prefix}"))
+    .replace("url(#", &format!("url(#{prefix}"))
+    .replace("href=\"#", &format!("href=\"#{prefix}"))
+}
+
+
+/*
+Export of a layout tree into an SVG document, e.g. for design handoff.
+Every element becomes a group holding what it paints and its children, named elements get their name as id.
+*/
+#[derive(Clone, Debug)]
+pub struct SvgExport {
+  pub width : u32, // logical pixels
+  pub height : u32,
+  pub unit_size : u32,
+  pub background : Option<Color>,
+  pub text : TextContext, // system fonts unless a font manager is given
+}
+
+
+impl SvgExport {
+
+  pub fn new(width : u32, height : u32) -> Self {
+    SvgExport { width, height, unit_size: 16, background: None, text: TextContext::default() }
+  }
+
+  pub fn unit_size(mut self, unit_size : u32) -> Self {
+    self.unit_size = unit_size;
+    self
+  }
+
+  /* Shapes text with the families registered in the font manager */
+  pub fn fonts(mut self, fonts : &FontManager) -> Self {
+    self.text = TextContext::new(fonts.collection());
+    self
+  }
+
+  /* Fills the document with this colour below the elements, without one the background stays transparent */
+  pub fn background(mut self, color : Color) -> Self {
+    self.background = Some(color);
+    self
+  }
+
+  /* Lays out the tree at the size of the document and writes it as SVG */
+  pub fn svg(&self, root : &mut dyn LayoutElement) -> Result<String, ImageError> {
+    prepare(root, self.unit_size)?;
+    root.calculate(self.width, self.height, self.unit_size, &self.text);
+    root.animate(Instant::now());
+
+    let (width, height) = (self.width, self.height);
+    let mut out = format!(
+      "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    );
+    if let Some(color) = self.background {
+      out += &format!(
+        "<rect width=\"{width}\" height=\"{height}\" fill=\"rgb({},{},{})\" fill-opacity=\"{}\"/>\n",
+        color.r(), color.g(), color.b(), color.a() as f32 / 255.0,
+      );
+    }
+
+    let mut writer = SvgWriter { export: self, out, ids: HashSet::new(), fragments: 0, clips: 0 };
+    writer.element(&*root);
+    writer.out += "</svg>\n";
+    Ok(writer.out)
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, root : &mut dyn LayoutElement, path : P) -> Result<(), ExportError> {
+    let path = path.as_ref();
+    let svg = self.svg(root)?;
+    Ok(write_file(path, svg)?)
+  }
+}
+
+
+/* Walks the tree in paint order, painting every element onto its own SVG canvas */
+struct SvgWriter<'a> {
+  export : &'a SvgExport,
+  out : String,
+  ids : HashSet<String>, // ids already issued
+  fragments : usize,
+  clips : usize,
+}
+
+
+impl SvgWriter<'_> {
+
+  fn element(&mut self, element : &dyn LayoutElement) {
+    let layout = element.layout();
+    if layout.current_style().opacity <= 0.0 { return }
+
+    let mut group = String::from("<g");
+    if let Some(name) = element.name() {
+      // Ids have to be unique, repeated names get the first counter no other element took yet
+      let mut id = name.to_string();
+      let mut count = 1;
+      while self.ids.contains(&id) {
+        count += 1;
+        id = format!("{name}-{count}");
+      }
+      group += &format!(" id=\"{}\"", escape(&id));
+      self.ids.insert(id);
+    }
+    // Same order of values in skia's affine matrix and SVG's
+    if let Some(DisplayItem::PushTransform([a, b, c, d, e, f])) = layout.transform_item() {
+      group += &format!(" transform=\"matrix({a} {b} {c} {d} {e} {f})\"");
+    }
+    // Group opacity fades the children with the element, as the layer does on screen
+    if let Some(DisplayItem::PushLayer { opacity }) = layout.layer_item() {
+      group += &format!(" opacity=\"{opacity}\"");
+    }
+    self.out += &group;
+    self.out += ">\n";
+
+    let unit_size = self.export.unit_size;
+    self.fragment(|list| element.display(list, unit_size));
+
+    let clipped = match layout.clip_item(unit_size) {
+      Some(DisplayItem::PushClip { bounds, radii }) => {
+        let path = skia_safe::Path::rrect(rounded_rect(bounds.into(), radii, [0.0; 4]), None).to_svg();
+        self.out += &format!("<clipPath id=\"clip{0}\"><path d=\"{path}\"/></clipPath>\n<g clip-path=\"url(#clip{0})\">\n", self.clips);
+        self.clips += 1;
+        true
+      }
+      _ => false,
+    };
+    for child in layout.children() {
+      self.element(child);
+    }
+    if clipped { self.out += "</g>\n"; }
+
+    self.fragment(|list| element.display_over(list, unit_size));
+
+    self.out += "</g>\n";
+  }
+
+  fn fragment(&mut self, display : impl FnOnce(&mut DisplayList)) {
+    let mut list = DisplayList::new();
+    display(&mut list);
+    if list.items().is_empty() { return }
+
+    let canvas = svg::Canvas::new(Rect::from_wh(self.export.width as f32, self.export.height as f32), None);
+    list.replay(&canvas);
+    let data = canvas.end();
+
+    let body = svg_body(&String::from_utf8_lossy(data.as_bytes()), &format!("f{}-", self.fragments));
+    self.fragments += 1;
+    if !body.is_empty() {
+      self.out += &body;
+      self.out += "\n";
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::gui::{container::Container, layout::Size};
+  use skia_safe::Color;
+
+  #[test]
+  fn test_page_count() {
+    assert_eq!(page_count(0.0, 100.0), 1);
+    assert_eq!(page_count(100.0, 100.0), 1);
+    assert_eq!(page_count(250.0, 100.0), 3);
+  }
+
+  #[test]
+  fn test_pdf() {
+    let export = PdfExport::new(A4).margins(10.0, 20.0, 30.0, 40.0);
+    assert_eq!(export.content_rect(), Rect::new(10.0, 30.0, 575.0, 802.0));
+
+    let mut root = Container::new("Report")
+      .width(Size::Max)
+      .height(Size::Unit(200.0))
+      .background(Color::RED);
+
+    let pdf = export.pdf(&mut root).unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+  }
+
+  #[test]
+  fn test_svg_body() {
+    let document = r#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" width="4" height="4"><defs><linearGradient id="g0"/></defs><rect fill="url(#g0)"/></svg>"#;
+    assert_eq!(svg_body(document, "f1-"), r#"<defs><linearGradient id="f1-g0"/></defs><rect fill="url(#f1-g0)"/>"#);
+  }
+
+  #[test]
+  fn test_svg_ids() {
+    let mut root = Container::new("Page")
+      .background(Color::WHITE)
+      .horizontal(vec![
+        Container::new("Card").width(Size::Unit(2.0)).background(Color::RED).boxed(),
+        Container::new("Card").width(Size::Unit(2.0)).background(Color::BLUE).boxed(),
+      ]);
+
+    let svg = SvgExport::new(40, 20).unit_size(10).svg(&mut root).unwrap();
+    assert!(svg.contains(r#"<g id="Page">"#));
+    assert!(svg.contains(r#"<g id="Card">"#));
+    assert!(svg.contains(r#"<g id="Card-2">"#));
+    assert!(svg.trim_end().ends_with("</svg>"));
+  }
+
+  #[test]
+  fn test_svg_ids_taken() {
+    // The generated id of the second card is also the name of the third element
+    let mut root = Container::new("Page").horizontal(vec![
+      Container::new("Card").boxed(),
+      Container::new("Card").boxed(),
+      Container::new("Card-2").boxed(),
+    ]);
+
+    let svg = SvgExport::new(40, 20).unit_size(10).svg(&mut root).unwrap();
+    assert_eq!(svg.matches(r#"id="Card-2""#).count(), 1);
+    assert!(svg.contains(r#"<g id="Card-2-2">"#));
+  }
+}
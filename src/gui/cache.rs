@@ -0,0 +1,214 @@
+use std::cell::{Cell, RefCell};
+
+use skia_safe::{Canvas, Picture, PictureRecorder, Rect};
+
+use super::{layout::{Layout, LayoutElement, Transform}, style::Style};
+use crate::objects::DisplayList;
+
+
+/* Reuse of the cached subtrees of a tree, counted since the tree was built */
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub struct CacheStats {
+  pub hits : u64,
+  pub misses : u64, // recordings, the first one of every boundary included
+}
+
+
+impl CacheStats {
+
+  /* Fraction of the paints served from a cached picture */
+  pub fn hit_rate(&self) -> f32 {
+    let total = self.hits + self.misses;
+    if total == 0 { return 0.0 }
+    self.hits as f32 / total as f32
+  }
+}
+
+impl std::ops::Add for CacheStats {
+  type Output = CacheStats;
+
+  fn add(self, other : CacheStats) -> CacheStats {
+    CacheStats { hits: self.hits + other.hits, misses: self.misses + other.misses }
+  }
+}
+
+
+/* What the picture was recorded from: frame, style, content revision and transform of every element, depth first */
+#[derive(PartialEq)]
+struct Key {
+  unit_size : u32,
+  elements : Vec<(Rect, Style, u64, Option<Transform>)>,
+}
+
+
+impl Key {
+
+  fn of(root : &dyn LayoutElement, unit_size : u32) -> Key {
+    let elements = root.iter().enumerate().map(|(i, element)| Key::entry(element.layout(), i == 0)).collect();
+    Key { unit_size, elements }
+  }
+
+  fn entry(layout : &Layout, root : bool) -> (Rect, Style, u64, Option<Transform>) {
+    let (mut style, mut transform) = (layout.current_style(), layout.transform());
+    // The root's own transform and opacity are applied outside of the picture
+    if root {
+      style.opacity = 1.0;
+      transform = None;
+    }
+    (layout.outer_rect(), style, layout.revision(), transform)
+  }
+}
+
+
+/* Area the subtree covers in the coordinates of its root, children may reach outside of the root's frame */
+fn bounds(root : &dyn LayoutElement) -> Rect {
+  let to_root = root.layout().window_matrix().invert().unwrap_or_default();
+  root.iter().fold(Rect::new_empty(), |mut bounds, element| {
+    bounds.join(to_root.map_rect(element.layout().window_rect()).0);
+    bounds
+  })
+}
+
+
+/*
+Picture of a subtree, recorded once and painted again as long as nothing in the subtree changes.
+Comparing the subtree is far cheaper than painting it, content changes are seen through Layout::damage.
+*/
+#[derive(Default)]
+pub struct PictureCache {
+  cached : RefCell<Option<(Key, Picture)>>,
+  hits : Cell<u64>,
+  misses : Cell<u64>,
+}
+
+
+impl PictureCache {
+
+  pub fn new() -> Self { Default::default() }
+
+  /* Drops the picture, the next paint records the subtree again */
+  pub fn invalidate(&self) {
+    self.cached.take();
+  }
+
+  pub fn stats(&self) -> CacheStats {
+    CacheStats { hits: self.hits.get(), misses: self.misses.get() }
+  }
+
+  /* Picture of the subtree below root, the one recorded before if the subtree is unchanged, else display records it anew */
+  pub fn picture(&self, root : &dyn LayoutElement, unit_size : u32, display : impl FnOnce(&mut DisplayList)) -> Option<Picture> {
+    let key = Key::of(root, unit_size);
+
+    if let Some((cached, picture)) = &*self.cached.borrow() {
+      if *cached == key {
+        self.hits.set(self.hits.get() + 1);
+        return Some(picture.clone());
+      }
+    }
+    self.misses.set(self.misses.get() + 1);
+
+    let mut list = DisplayList::new();
+    display(&mut list);
+
+    let mut recorder = PictureRecorder::new();
+    list.replay(recorder.begin_recording(bounds(root), None));
+    let picture = recorder.finish_recording_as_picture(None)?;
+
+    *self.cached.borrow_mut() = Some((key, picture.clone()));
+    Some(picture)
+  }
+}
+
+
+/*
+Picture of what a canvas element's painter draws, kept while the element's frame, style and revision stay the same.
+The same picture in consecutive frames compares equal, so display list diffs only see drawings that changed.
+*/
+#[derive(Default)]
+pub struct Recording {
+  cached : RefCell<Option<(Key, Option<Picture>)>>,
+}
+
+
+impl Recording {
+
+  /* Picture of the element's drawing, recorded by paint if the element changed, None if it draws nothing */
+  pub fn picture(&self, layout : &Layout, unit_size : u32, paint : impl FnOnce(&Canvas)) -> Option<Picture> {
+    let key = Key { unit_size, elements: vec![Key::entry(layout, true)] };
+
+    if let Some((cached, picture)) = &*self.cached.borrow() {
+      if *cached == key { return picture.clone() }
+    }
+
+    let mut recorder = PictureRecorder::new();
+    paint(recorder.begin_recording(layout.outer_rect(), None));
+    let picture = recorder.finish_recording_as_picture(None).filter(|picture| picture.approximate_op_count() > 0);
+
+    *self.cached.borrow_mut() = Some((key, picture.clone()));
+    picture
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use skia_safe::Color;
+
+  use super::*;
+  use crate::{gui::{canvas::CanvasElement, container::Container, layout::Size}, testing::{lay_out, layout_of}};
+
+  #[test]
+  fn test_picture_cache() {
+    let mut root = Container::new("Root")
+      .width(Size::Max)
+      .height(Size::Max)
+      .horizontal(vec![
+        Container::new("Panel").cached().background(Color::RED).vertical(vec![
+          Container::new("Item").width(Size::Unit(2.0)).height(Size::Unit(2.0)).background(Color::BLUE).boxed(),
+        ]).boxed(),
+      ]);
+    let root: &mut dyn LayoutElement = &mut root;
+
+    let frame = |root: &mut dyn LayoutElement| {
+      lay_out(root);
+      root.display_list(10)
+    };
+
+    let first = frame(root);
+    let second = frame(root);
+    assert_eq!(root.cache_stats(), CacheStats { hits: 1, misses: 1 });
+    assert!(second.diff(&first).is_empty());
+
+    // Changes anywhere below the boundary record it again
+    layout_of(root, "Item").damage();
+    let third = frame(root);
+    assert_eq!(root.cache_stats(), CacheStats { hits: 1, misses: 2 });
+    assert_eq!(third.diff(&second), vec![0]);
+
+    frame(root);
+    assert_eq!(root.cache_stats(), CacheStats { hits: 2, misses: 2 });
+  }
+
+  #[test]
+  fn test_recording() {
+    let mut root = Container::new("Root")
+      .width(Size::Max)
+      .height(Size::Max)
+      .horizontal(vec![
+        CanvasElement::new(|canvas, _| { canvas.clear(Color::RED); }).width(Size::Unit(2.0)).height(Size::Unit(2.0)).boxed(),
+      ]);
+    let root: &mut dyn LayoutElement = &mut root;
+
+    lay_out(root);
+    let first = root.display_list(10);
+    lay_out(root);
+    let second = root.display_list(10);
+    assert_eq!(first.items().len(), 1);
+    assert!(second.diff(&first).is_empty());
+
+    // Damage records the drawing again
+    root.iter_mut().nth(1).unwrap().layout_mut().damage();
+    lay_out(root);
+    assert_eq!(root.display_list(10).diff(&second), vec![0]);
+  }
+}
@@ -0,0 +1,117 @@
+use skia_safe::{Canvas, Rect};
+
+use crate::{objects::{DisplayItem, DisplayList}, user_event::UserEvent};
+
+use super::{cache::Recording, layout::{Layout, LayoutElement}};
+
+
+/*
+Element drawing arbitrary content (plots, waveforms, ...) with a callback.
+The callback gets the canvas translated to the top left corner of the content box and clipped to it,
+together with the size of the content box, so it draws in its own coordinates.
+The drawing is recorded once and replayed until the layout is damaged, e.g. by set_painter or a consumed event.
+*/
+pub struct CanvasElement {
+  layout : Layout,
+
+  painter : Box<dyn Fn(&Canvas, (f32, f32))>,
+  on_event : Option<Box<dyn FnMut(&UserEvent, (f32, f32)) -> bool>>,
+
+  recording : Recording, // what the painter drew for the current revision
+}
+
+
+impl LayoutElement for CanvasElement {
+  fn layout(&self) -> &Layout {
+    &self.layout
+  }
+
+  fn layout_mut(&mut self) -> &mut Layout {
+    &mut self.layout
+  }
+
+  fn display(&self, list: &mut DisplayList, unit_size: u32) {
+    list.extend(self.layout.current_style().fill_item(self.layout.inner_rect(), unit_size));
+    list.extend(self.recording.picture(&self.layout, unit_size, |canvas| self.paint(canvas)).map(DisplayItem::Picture));
+  }
+
+  fn event(&mut self, event: &UserEvent) -> bool {
+    let Some(handler) = &mut self.on_event else { return false };
+    let (x, y) = self.layout.computed.content_pos;
+    // A consumed event usually changes what the painter draws
+    let consumed = handler(event, (x as f32, y as f32));
+    if consumed { self.layout.damage(); }
+    consumed
+  }
+}
+
+impl CanvasElement {
+
+  pub fn new(painter : impl Fn(&Canvas, (f32, f32)) + 'static) -> Self {
+    CanvasElement { layout: Default::default(), painter: Box::new(painter), on_event: None, recording: Default::default() }
+  }
+
+  /* Replaces the callback, e.g. to draw new data, and repaints the element */
+  pub fn set_painter(&mut self, painter : impl Fn(&Canvas, (f32, f32)) + 'static) {
+    self.painter = Box::new(painter);
+    self.layout.damage();
+  }
+
+  /*
+  Handles the events hitting the element, the handler gets the position of the content box to make
+  event positions relative to it, returns true if it consumed the event
+  */
+  pub fn on_event(mut self, handler : impl FnMut(&UserEvent, (f32, f32)) -> bool + 'static) -> Self {
+    self.on_event = Some(Box::new(handler));
+    self
+  }
+
+  /* Runs the painter in the coordinates of the content box, clipped to it */
+  fn paint(&self, canvas : &Canvas) {
+    let computed = &self.layout.computed;
+    let size = (computed.content_dim.0 as f32, computed.content_dim.1 as f32);
+
+    let count = canvas.save();
+    canvas.translate((computed.content_pos.0 as f32, computed.content_pos.1 as f32));
+    canvas.clip_rect(Rect::from_wh(size.0, size.1), None, true);
+
+    (self.painter)(canvas, size);
+
+    canvas.restore_to_count(count);
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use skia_safe::{surfaces, Color, Paint};
+
+  use super::*;
+  use crate::gui::{layout::Size, text::TextContext};
+
+  #[test]
+  fn test_clipped_to_content() {
+    let mut element = CanvasElement::new(|canvas, (width, height)| {
+      assert_eq!((width, height), (10.0, 10.0));
+
+      // Deliberately paints far outside the content box
+      let mut paint = Paint::default();
+      paint.set_color(Color::RED);
+      canvas.draw_rect(Rect::new(-100.0, -100.0, 100.0, 100.0), &paint);
+    })
+    .width(Size::Unit(4.0))
+    .height(Size::Unit(4.0))
+    .margin(1.0);
+
+    element.calculate(20, 20, 5, &TextContext::default());
+
+    let mut surface = surfaces::raster_n32_premul((20, 20)).unwrap();
+    (&element as &dyn LayoutElement).paint_tree(surface.canvas(), 5);
+
+    let pixels = surface.peek_pixels().unwrap();
+    assert_eq!(pixels.get_color((10, 10)), Color::RED);
+    assert_eq!(pixels.get_color((2, 2)), Color::TRANSPARENT);
+    assert_eq!(pixels.get_color((17, 17)), Color::TRANSPARENT);
+    assert_eq!(surface.canvas().save_count(), 1);
+  }
+}
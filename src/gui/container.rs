@@ -8,6 +8,10 @@ pub struct Container {
 
 
 impl LayoutElement for Container {
+  fn name(&self) -> Option<&str> {
+    Some(&self.name)
+  }
+
   fn layout(&self) -> &Layout {
     &self.layout
   }
@@ -0,0 +1,152 @@
+use std::{fmt, io, path::{Path, PathBuf}};
+
+use skia_safe::{textlayout::{FontCollection, TypefaceFontProvider}, FontMgr};
+
+use super::layout::LayoutElement;
+
+
+#[derive(Debug)]
+pub enum FontError {
+  Io(PathBuf, io::Error),
+  InvalidFont(String),          // file or description of the data that couldn't be parsed as a font
+  UnknownFamily(String, String), // family and the element that requested it
+}
+
+
+impl fmt::Display for FontError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FontError::Io(path, error) => write!(f, "could not read font file {}: {error}", path.display()),
+      FontError::InvalidFont(source) => write!(f, "{source} is not a valid TrueType or OpenType font"),
+      FontError::UnknownFamily(family, element) =>
+        write!(f, "font family \"{family}\" requested by {element} is neither registered nor installed on the system"),
+    }
+  }
+}
+
+impl std::error::Error for FontError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      FontError::Io(_, error) => Some(error),
+      _ => None,
+    }
+  }
+}
+
+
+/*
+Registry of the font families the application ships with.
+Registered families are looked up before the system fonts, so they can also replace an installed family.
+*/
+pub struct FontManager {
+  provider : TypefaceFontProvider,
+  system : FontMgr,
+  families : Vec<String>,
+}
+
+
+impl Default for FontManager {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+
+impl FontManager {
+
+  pub fn new() -> Self {
+    FontManager { provider: TypefaceFontProvider::new(), system: FontMgr::new(), families: Vec::new() }
+  }
+
+  /* Registers a .ttf or .otf file, returns the family name it is available under */
+  pub fn register_file<P: AsRef<Path>>(&mut self, path : P) -> Result<String, FontError> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).map_err(|error| FontError::Io(path.to_path_buf(), error))?;
+    self.register(&data, None, || path.display().to_string())
+  }
+
+  /* Registers font data, e.g. embedded with include_bytes!, returns the family name it is available under */
+  pub fn register_bytes(&mut self, data : &[u8]) -> Result<String, FontError> {
+    self.register(data, None, || format!("font data of {} bytes", data.len()))
+  }
+
+  /* Registers font data under a family name of our choosing instead of the one stored in the font */
+  pub fn register_bytes_as(&mut self, data : &[u8], family : &str) -> Result<String, FontError> {
+    self.register(data, Some(family), || format!("font data for \"{family}\""))
+  }
+
+  fn register(&mut self, data : &[u8], alias : Option<&str>, source : impl FnOnce() -> String) -> Result<String, FontError> {
+    let typeface = self.system.new_from_data(data, None).ok_or_else(|| FontError::InvalidFont(source()))?;
+    let family = alias.map_or_else(|| typeface.family_name(), str::to_string);
+
+    self.provider.register_typeface(typeface, alias);
+    if !self.families.contains(&family) {
+      self.families.push(family.clone());
+    }
+    Ok(family)
+  }
+
+  /* Families registered with the manager, in registration order */
+  pub fn families(&self) -> impl Iterator<Item = &str> {
+    self.families.iter().map(String::as_str)
+  }
+
+  /* True if the family was registered or is installed on the system */
+  pub fn is_available(&self, family : &str) -> bool {
+    self.families.iter().any(|f| f.eq_ignore_ascii_case(family)) || self.system.match_family(family).count() > 0
+  }
+
+  /* Checks that every font family used in the tree can be resolved */
+  pub fn check(&self, root : &dyn LayoutElement) -> Result<(), FontError> {
+    for element in root.iter() {
+      if let Some(family) = element.font_families().into_iter().find(|family| !self.is_available(family)) {
+        let name = element.name().map_or_else(|| "an unnamed element".to_string(), |name| format!("\"{name}\""));
+        return Err(FontError::UnknownFamily(family.to_string(), name));
+      }
+    }
+    Ok(())
+  }
+
+  /* Collection looking up the registered families first, then the system fonts and their fallback */
+  pub fn collection(&self) -> FontCollection {
+    let mut collection = FontCollection::new();
+    collection.set_asset_font_manager(FontMgr::from(self.provider.clone()));
+    collection.set_default_font_manager(self.system.clone(), None);
+    collection.enable_font_fallback();
+    collection
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::gui::{layout::LayoutElement, text::TextElement};
+
+  const LATIN: &[u8] = include_bytes!("../../assets/fonts/Cantarell-Regular.ttf");
+
+  #[test]
+  fn test_register() {
+    let mut fonts = FontManager::new();
+
+    assert_eq!(fonts.register_bytes(LATIN).unwrap(), "Cantarell");
+    assert_eq!(fonts.register_bytes_as(LATIN, "Brand").unwrap(), "Brand");
+    assert_eq!(fonts.families().collect::<Vec<_>>(), ["Cantarell", "Brand"]);
+
+    assert!(matches!(fonts.register_bytes(b"not a font"), Err(FontError::InvalidFont(_))));
+    assert!(matches!(fonts.register_file("missing.ttf"), Err(FontError::Io(..))));
+  }
+
+  #[test]
+  fn test_unknown_family() {
+    let mut fonts = FontManager::new();
+    fonts.register_bytes_as(LATIN, "Brand").unwrap();
+
+    let known = TextElement::new("Hello").font_family("Brand");
+    assert!(fonts.check(&known as &dyn LayoutElement).is_ok());
+
+    let unknown = TextElement::new("Hello").font_family("No Such Family 1234");
+    let error = fonts.check(&unknown as &dyn LayoutElement).unwrap_err();
+    assert!(matches!(&error, FontError::UnknownFamily(family, _) if family == "No Such Family 1234"));
+  }
+}
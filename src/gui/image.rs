@@ -0,0 +1,365 @@
+use std::{collections::HashMap, fmt, io, path::PathBuf, sync::Arc};
+
+use skia_safe::{codec::{jpeg_decoder, png_decoder, webp_decoder}, Codec, CubicResampler, Data, FilterMode, Image, MipmapMode, Rect, SamplingOptions};
+
+use super::{layout::{Layout, LayoutElement}, text::TextContext};
+#[cfg(feature = "svg")]
+use super::svg::SvgImage;
+use crate::objects::{DisplayItem, DisplayList};
+
+
+/* Where an image is loaded from, also the key of the image cache */
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum ImageSource {
+  File(PathBuf),
+  Bytes(Arc<[u8]>), // encoded PNG, JPEG or WebP data, e.g. embedded with include_bytes!
+}
+
+
+impl From<&str> for ImageSource {
+  fn from(path: &str) -> Self { ImageSource::File(path.into()) }
+}
+
+impl From<PathBuf> for ImageSource {
+  fn from(path: PathBuf) -> Self { ImageSource::File(path) }
+}
+
+impl From<&[u8]> for ImageSource {
+  fn from(data: &[u8]) -> Self { ImageSource::Bytes(data.into()) }
+}
+
+impl From<Vec<u8>> for ImageSource {
+  fn from(data: Vec<u8>) -> Self { ImageSource::Bytes(data.into()) }
+}
+
+
+#[derive(Debug)]
+pub enum ImageError {
+  Io(PathBuf, io::Error),
+  Decode(String), // source that isn't a supported image (PNG, JPEG, WebP or SVG)
+}
+
+
+impl fmt::Display for ImageError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ImageError::Io(path, error) => write!(f, "could not read image file {}: {error}", path.display()),
+      ImageError::Decode(source) => write!(f, "could not decode {source}, it is not a supported image"),
+    }
+  }
+}
+
+impl std::error::Error for ImageError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ImageError::Io(_, error) => Some(error),
+      _ => None,
+    }
+  }
+}
+
+
+/* Decodes PNG, JPEG or WebP data into a raster image */
+pub fn decode(data: &[u8]) -> Option<Image> {
+  let decoders = [png_decoder::decoder(), jpeg_decoder::decoder(), webp_decoder::decoder()];
+  let mut codec = Codec::from_data_with_decoders(Data::new_copy(data), &decoders)?;
+  codec.get_image(None, None).ok()
+}
+
+
+/* Decoded images shared by all image elements, so every source is read and decoded once */
+#[derive(Default)]
+pub struct ImageCache {
+  images : HashMap<ImageSource, Image>,
+  #[cfg(feature = "svg")]
+  svgs : HashMap<ImageSource, SvgImage>,
+}
+
+
+impl ImageCache {
+
+  pub fn new() -> Self { Default::default() }
+
+  /* Decoded image of the source, decoding it on first use */
+  pub fn load(&mut self, source : &ImageSource) -> Result<Image, ImageError> {
+    if let Some(image) = self.images.get(source) {
+      return Ok(image.clone());
+    }
+
+    let image = read(source, decode)?;
+    self.images.insert(source.clone(), image.clone());
+    Ok(image)
+  }
+
+  /* Parsed SVG document of the source, parsing it on first use */
+  #[cfg(feature = "svg")]
+  pub fn load_svg(&mut self, source : &ImageSource) -> Result<SvgImage, ImageError> {
+    if let Some(image) = self.svgs.get(source) {
+      return Ok(image.clone());
+    }
+
+    let image = read(source, SvgImage::parse)?;
+    self.svgs.insert(source.clone(), image.clone());
+    Ok(image)
+  }
+
+  pub fn len(&self) -> usize {
+    let len = self.images.len();
+    #[cfg(feature = "svg")]
+    let len = len + self.svgs.len();
+    len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /* Drops the decoded image, e.g. after the file changed, the next load decodes it again */
+  pub fn remove(&mut self, source : &ImageSource) {
+    self.images.remove(source);
+    #[cfg(feature = "svg")]
+    self.svgs.remove(source);
+  }
+
+  pub fn clear(&mut self) {
+    self.images.clear();
+    #[cfg(feature = "svg")]
+    self.svgs.clear();
+  }
+}
+
+
+/* Reads the data of the source and decodes it with the given decoder */
+fn read<T>(source : &ImageSource, decode : impl Fn(&[u8]) -> Option<T>) -> Result<T, ImageError> {
+  match source {
+    ImageSource::File(path) => {
+      let data = std::fs::read(path).map_err(|error| ImageError::Io(path.clone(), error))?;
+      decode(&data).ok_or_else(|| ImageError::Decode(path.display().to_string()))
+    }
+    ImageSource::Bytes(data) => decode(data).ok_or_else(|| ImageError::Decode(format!("image data of {} bytes", data.len()))),
+  }
+}
+
+
+/* How the image is fitted into the content box, as CSS object-fit */
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Fit {
+  Contain, // scaled to fit inside the box, keeping its aspect ratio
+  Cover,   // scaled to cover the whole box, keeping its aspect ratio and cropping the rest
+  Fill,    // stretched to the box
+  None,    // drawn at its own size, cropped to the box
+}
+
+
+impl Fit {
+
+  /* Part of the image (src) and where to draw it (dst) for an image of the given size, centred in the box */
+  pub fn rects(&self, image : (f32, f32), bounds : Rect) -> (Rect, Rect) {
+    let (width, height) = image;
+    let full = Rect::from_wh(width, height);
+    if width <= 0.0 || height <= 0.0 {
+      return (full, Rect::from_xywh(bounds.center_x(), bounds.center_y(), 0.0, 0.0));
+    }
+
+    let scale = match self {
+      Fit::Fill => return (full, bounds),
+      Fit::Contain => (bounds.width() / width).min(bounds.height() / height),
+      Fit::Cover => (bounds.width() / width).max(bounds.height() / height),
+      Fit::None => 1.0,
+    };
+
+    // Scaled image centred on the box, then both rects are cut down to the part inside the box
+    let dst = Rect::from_xywh(
+      bounds.center_x() - width * scale / 2.0,
+      bounds.center_y() - height * scale / 2.0,
+      width * scale,
+      height * scale,
+    );
+    let mut visible = dst;
+    if !visible.intersect(bounds) {
+      return (full, Rect::from_xywh(bounds.center_x(), bounds.center_y(), 0.0, 0.0));
+    }
+
+    let src = Rect::new(
+      (visible.left - dst.left) / scale,
+      (visible.top - dst.top) / scale,
+      (visible.right - dst.left) / scale,
+      (visible.bottom - dst.top) / scale,
+    );
+    (src, visible)
+  }
+}
+
+
+/* Filtering used when the image is scaled */
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Sampling {
+  Nearest, // crisp pixels, for pixel art
+  Linear,
+  Mipmap,  // linear with mipmaps, for strongly downscaled images
+  Cubic,   // Mitchell filter, best quality for upscaling
+}
+
+
+impl From<Sampling> for SamplingOptions {
+  fn from(sampling: Sampling) -> Self {
+    match sampling {
+      Sampling::Nearest => SamplingOptions::new(FilterMode::Nearest, MipmapMode::None),
+      Sampling::Linear => SamplingOptions::new(FilterMode::Linear, MipmapMode::None),
+      Sampling::Mipmap => SamplingOptions::new(FilterMode::Linear, MipmapMode::Linear),
+      Sampling::Cubic => CubicResampler::mitchell().into(),
+    }
+  }
+}
+
+
+pub struct ImageElement {
+  source : ImageSource,
+  layout : Layout,
+
+  fit : Fit,
+  sampling : Sampling,
+
+  image : Option<Image>,
+  stale : bool, // source changed since images were last loaded
+}
+
+
+impl LayoutElement for ImageElement {
+  fn layout(&self) -> &Layout {
+    &self.layout
+  }
+
+  fn layout_mut(&mut self) -> &mut Layout {
+    &mut self.layout
+  }
+
+  fn load_images(&mut self, images: &mut ImageCache) -> Result<(), ImageError> {
+    self.image = None;
+    self.stale = false;
+    self.image = Some(images.load(&self.source)?);
+    Ok(())
+  }
+
+  fn images_stale(&self) -> bool {
+    self.stale
+  }
+
+  fn measure(&mut self, _width: u32, _height: u32, _unit_size: u32, _text: &TextContext) -> Option<(u32, u32)> {
+    self.image.as_ref().map(|image| (image.width() as u32, image.height() as u32))
+  }
+
+  fn display(&self, list: &mut DisplayList, unit_size: u32) {
+    let style = self.layout.current_style();
+    list.extend(style.fill_item(self.layout.inner_rect(), unit_size));
+
+    let Some(image) = &self.image else { return };
+    let computed = &self.layout.computed;
+    let bounds = Rect::from_xywh(
+      computed.content_pos.0 as f32,
+      computed.content_pos.1 as f32,
+      computed.content_dim.0 as f32,
+      computed.content_dim.1 as f32,
+    );
+
+    let (src, dst) = self.fit.rects((image.width() as f32, image.height() as f32), bounds);
+    if dst.is_empty() { return }
+
+    list.push(DisplayItem::Image { image: image.clone(), src: src.into(), dst: dst.into(), sampling: self.sampling });
+  }
+}
+
+impl ImageElement {
+
+  pub fn new<S: Into<ImageSource>>(source : S) -> Self {
+    ImageElement {
+      source: source.into(),
+      layout: Default::default(),
+      fit: Fit::Contain,
+      sampling: Sampling::Linear,
+      image: None,
+      stale: false,
+    }
+  }
+
+  pub fn source(&self) -> &ImageSource {
+    &self.source
+  }
+
+  /* Shows another image, the application loads it through its cache before the next frame is laid out */
+  pub fn set_source<S: Into<ImageSource>>(&mut self, source : S) {
+    self.source = source.into();
+    self.image = None;
+    self.stale = true;
+    self.layout.damage();
+  }
+
+  pub fn fit(mut self, fit : Fit) -> Self {
+    self.fit = fit;
+    self
+  }
+
+  pub fn sampling(mut self, sampling : Sampling) -> Self {
+    self.sampling = sampling;
+    self
+  }
+
+  /* Decoded image, None until loaded or if loading failed */
+  pub fn image(&self) -> Option<&Image> {
+    self.image.as_ref()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PNG: &[u8] = include_bytes!("../../assets/images/swatch.png");
+
+  #[test]
+  fn test_cache() {
+    let mut cache = ImageCache::new();
+    let source = ImageSource::from(PNG);
+
+    let first = cache.load(&source).unwrap();
+    let second = cache.load(&source).unwrap();
+    assert_eq!((first.width(), first.height()), (4, 2));
+    assert_eq!(first.unique_id(), second.unique_id());
+    assert_eq!(cache.len(), 1);
+
+    assert!(matches!(cache.load(&ImageSource::from(&b"not an image"[..])), Err(ImageError::Decode(_))));
+    assert!(matches!(cache.load(&ImageSource::from("missing.png")), Err(ImageError::Io(..))));
+    assert_eq!(cache.len(), 1);
+  }
+
+  #[test]
+  fn test_set_source() {
+    let mut cache = ImageCache::new();
+    let mut element = ImageElement::new(PNG);
+    element.load_images(&mut cache).unwrap();
+    assert!(!element.images_stale());
+
+    element.set_source("missing.png");
+    assert!(element.images_stale() && element.image().is_none());
+
+    // A failed load isn't retried every frame
+    assert!(element.load_images(&mut cache).is_err());
+    assert!(!element.images_stale());
+
+    element.set_source(PNG);
+    element.load_images(&mut cache).unwrap();
+    assert!(element.image().is_some());
+  }
+
+  #[test]
+  fn test_fit() {
+    let bounds = Rect::from_xywh(0.0, 0.0, 100.0, 50.0);
+    let image = (200.0, 200.0);
+
+    assert_eq!(Fit::Fill.rects(image, bounds), (Rect::from_wh(200.0, 200.0), bounds));
+    assert_eq!(Fit::Contain.rects(image, bounds), (Rect::from_wh(200.0, 200.0), Rect::from_xywh(25.0, 0.0, 50.0, 50.0)));
+    assert_eq!(Fit::Cover.rects(image, bounds), (Rect::from_xywh(0.0, 50.0, 200.0, 100.0), bounds));
+    assert_eq!(Fit::None.rects(image, bounds), (Rect::from_xywh(50.0, 75.0, 100.0, 50.0), bounds));
+  }
+}
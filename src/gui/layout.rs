@@ -19,7 +19,13 @@ Okay so how do we work on overflow components
 
 */
 
-use std::cmp::max;
+use std::{cmp::max, time::{Duration, Instant}};
+
+use skia_safe::{Canvas, Color, Matrix, RRect, Rect};
+
+use crate::{objects::{DisplayItem, DisplayList}, scheduler::RedrawHandle, user_event::UserEvent};
+
+use super::{cache::{CacheStats, PictureCache}, image::{ImageCache, ImageError}, style::{rounded_rect, rrect_contains, State, Style, Variants}, text::TextContext, theme::Properties};
 
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Size {
@@ -33,7 +39,7 @@ pub enum Size {
 }
 
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Axis {
   Horizontal,
   Vertical
@@ -49,6 +55,77 @@ pub enum Alignment {
 }
 
 
+/* Part of the element its children are clipped to */
+#[derive(PartialEq, Clone, Copy, Default, Debug)]
+pub enum Clip {
+  #[default]
+  None,
+  Rect,    // frame inside the margin
+  Rounded, // frame inside the margin, with the corner radii of the style
+}
+
+
+/*
+Affine transform of an element and its children, applied when painting without changing the layout.
+Scaling and rotation (degrees, clockwise) happen around the origin, a fraction of the frame inside the margin.
+*/
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Transform {
+  pub translate: (f32, f32), // in units
+  pub scale: (f32, f32),
+  pub rotate: f32,
+  pub origin: (f32, f32),
+}
+
+
+impl Default for Transform {
+  fn default() -> Self {
+    Transform { translate: (0.0, 0.0), scale: (1.0, 1.0), rotate: 0.0, origin: (0.5, 0.5) }
+  }
+}
+
+impl Transform {
+
+  pub fn new() -> Self { Default::default() }
+
+  pub fn translate(mut self, x: f32, y: f32) -> Self {
+    self.translate = (x, y);
+    self
+  }
+
+  pub fn scale(self, scale: f32) -> Self {
+    self.scale_xy(scale, scale)
+  }
+
+  pub fn scale_xy(mut self, x: f32, y: f32) -> Self {
+    self.scale = (x, y);
+    self
+  }
+
+  pub fn rotate(mut self, degrees: f32) -> Self {
+    self.rotate = degrees;
+    self
+  }
+
+  pub fn origin(mut self, x: f32, y: f32) -> Self {
+    self.origin = (x, y);
+    self
+  }
+
+  /* Matrix from the coordinates of the element with the given frame into those of its parent */
+  pub fn matrix(&self, frame: Rect, unit_size: u32) -> Matrix {
+    let origin = (frame.left + frame.width() * self.origin.0, frame.top + frame.height() * self.origin.1);
+    let unit = unit_size as f32;
+
+    let mut matrix = Matrix::translate((origin.0 + self.translate.0 * unit, origin.1 + self.translate.1 * unit));
+    matrix.pre_rotate(self.rotate, None);
+    matrix.pre_scale(self.scale, None);
+    matrix.pre_translate((-origin.0, -origin.1));
+    matrix
+  }
+}
+
+
 #[derive(PartialEq, Clone, Copy, Default)]
 pub struct ComputedLayout {
   pub outer_dim : (u32, u32),
@@ -65,9 +142,9 @@ pub struct ComputedLayout {
 }
 
 pub struct Layout {
-  margin: [f32; 4],
-  padding: [f32; 4],
-  border: [f32; 4],
+  pub(crate) margin: [f32; 4],
+  pub(crate) padding: [f32; 4],
+  pub(crate) border: [f32; 4],
 
   axis: Axis,
   
@@ -79,7 +156,26 @@ pub struct Layout {
 
   pub computed : ComputedLayout,
 
+  pub style: Style,
+  pub variants: Variants,
+  pub(crate) classes: Vec<String>,
+  pub(crate) base: Option<Box<Properties>>, // every property as it was before the first theme was applied
+  pub(crate) inline: Properties, // set on the element directly, wins over theme rules
+  pub(crate) themed: Option<Box<Properties>>, // every property as the last theme left it, to see later edits
+  intrinsic: Option<(u32, u32)>, // measured content size of the element itself
+
   children: Vec<Box<dyn LayoutElement>>,
+
+  damaged: bool, // content changed in a way position and style don't show, e.g. new text
+  revision: u64,  // counts the damage reports, tells cached pictures the content changed
+
+  cache: Option<PictureCache>, // set on cache boundaries
+
+  clip: Clip,
+  transform: Option<Transform>,
+  local_matrix: Matrix,  // resolved transform, into the coordinates of the parent
+  window_matrix: Matrix, // from the layout coordinates of the element into the window, ancestors included
+  ancestor_clips: Vec<(Matrix, RRect)>, // clip areas of the clipping ancestors, each with the matrix from the window into its coordinates
 }
 
 
@@ -95,7 +191,22 @@ impl Default for Layout {
           halign: Alignment::Start, 
           valign: Alignment::Start, 
           computed: Default::default(),
-          children: Default::default() 
+          style: Default::default(),
+          variants: Default::default(),
+          classes: Default::default(),
+          base: None,
+          inline: Default::default(),
+          themed: None,
+          intrinsic: None,
+          children: Default::default(),
+          damaged: false,
+          revision: 0,
+          cache: None,
+          clip: Clip::None,
+          transform: None,
+          local_matrix: Matrix::default(),
+          window_matrix: Matrix::default(),
+          ancestor_clips: Vec::new(),
       }
     }
 }
@@ -106,6 +217,12 @@ pub trait LayoutElement {
   fn layout(&self) -> &Layout;
   fn layout_mut(&mut self) -> &mut Layout;
 
+  /* Name of the element, used as id by theme selectors */
+  fn name(&self) -> Option<&str> { None }
+
+  /* Font families the element draws its text with, checked against the font manager at startup */
+  fn font_families(&self) -> Vec<&str> { Vec::new() }
+
   fn boxed(self) -> Box<dyn LayoutElement> where Self : Sized + 'static {
     Box::new(self)
   }
@@ -166,6 +283,7 @@ pub trait LayoutElement {
     layout.margin[1] = right;
     layout.margin[2] = top;
     layout.margin[3] = bottom;   
+    layout.inline.margin = Some(layout.margin);
     self
   }
   
@@ -193,6 +311,7 @@ pub trait LayoutElement {
     layout.padding[1] = right;
     layout.padding[2] = top;
     layout.padding[3] = bottom;   
+    layout.inline.padding = Some(layout.padding);
     self
   }
   
@@ -201,27 +320,211 @@ pub trait LayoutElement {
   where
     Self: Sized,
   {
-    self.layout_mut().hsize = sizing;
-    self
+    self.properties(Properties::new().width(sizing))
   }
 
   fn height(mut self, sizing: Size) -> Self
   where
     Self: Sized,
   {
-    self.layout_mut().vsize = sizing;
+    self.properties(Properties::new().height(sizing))
+  }
+
+  fn class<T: Into<String>>(mut self, class: T) -> Self
+  where
+    Self: Sized,
+  {
+    self.layout_mut().classes.push(class.into());
     self
   }
 
-  fn calculate(&mut self, width: u32, height: u32, unit_size: u32) {
+  /* Sets properties on the element itself, like inline styles they win over every theme rule */
+  fn properties(mut self, properties: Properties) -> Self
+  where
+    Self: Sized,
+  {
     let layout = self.layout_mut();
-    let layout_info = LayoutInfo { width, height, x: 0, y: 0, unit_size };
+    properties.apply(layout);
+    layout.inline.merge(&properties);
+    self
+  }
+
+  /* Replaces the whole style, every style property then counts as set on the element */
+  fn style(self, style: Style) -> Self
+  where
+    Self: Sized,
+  {
+    self.properties(Properties::of_style(&style))
+  }
+
+  fn background(self, color: Color) -> Self
+  where
+    Self: Sized,
+  {
+    self.properties(Properties::new().background(color))
+  }
+
+  fn gradient(self, from: Color, to: Color, axis: Axis) -> Self
+  where
+    Self: Sized,
+  {
+    self.properties(Properties::new().gradient(from, to, axis))
+  }
+
+  fn border(self, width: f32, color: Color) -> Self
+  where
+    Self: Sized,
+  {
+    self.properties(Properties::new().border(width, color))
+  }
+
+  fn rounded(self, radius: f32) -> Self
+  where
+    Self: Sized,
+  {
+    self.properties(Properties::new().rounded(radius))
+  }
+
+  fn rounded_all(self, top_left: f32, top_right: f32, bottom_right: f32, bottom_left: f32) -> Self
+  where
+    Self: Sized,
+  {
+    self.properties(Properties::new().rounded_all(top_left, top_right, bottom_right, bottom_left))
+  }
+
+  fn opacity(self, opacity: f32) -> Self
+  where
+    Self: Sized,
+  {
+    self.properties(Properties::new().opacity(opacity))
+  }
+
+  /* Foreground colour, used for text */
+  fn color(self, color: Color) -> Self
+  where
+    Self: Sized,
+  {
+    self.properties(Properties::new().color(color))
+  }
+
+  /* Style properties applied on top while the element is in the given state */
+  fn on_state(mut self, state: State, properties: Properties) -> Self
+  where
+    Self: Sized,
+  {
+    self.layout_mut().variants.own.push((state, properties));
+    self
+  }
+
+  fn hovered(self, properties: Properties) -> Self
+  where
+    Self: Sized,
+  {
+    self.on_state(State::Hovered, properties)
+  }
+
+  fn pressed(self, properties: Properties) -> Self
+  where
+    Self: Sized,
+  {
+    self.on_state(State::Pressed, properties)
+  }
+
+  fn focused(self, properties: Properties) -> Self
+  where
+    Self: Sized,
+  {
+    self.on_state(State::Focused, properties)
+  }
+
+  fn disabled(mut self, disabled: bool) -> Self
+  where
+    Self: Sized,
+  {
+    self.layout_mut().variants.interaction.disabled = disabled;
+    self
+  }
+
+  /* Paints the element and its children from a picture recorded once, for large parts that rarely change */
+  fn cached(mut self) -> Self
+  where
+    Self: Sized,
+  {
+    self.layout_mut().set_cached(true);
+    self
+  }
+
+  /* Clips the children to the element's frame */
+  fn clip(mut self, clip: Clip) -> Self
+  where
+    Self: Sized,
+  {
+    self.layout_mut().set_clip(clip);
+    self
+  }
+
+  /* Moves, scales or rotates the element and its children when painting, hit testing follows */
+  fn transform(mut self, transform: Transform) -> Self
+  where
+    Self: Sized,
+  {
+    self.layout_mut().set_transform(Some(transform));
+    self
+  }
+
+  /* Animates between state variants over the given duration */
+  fn transition(mut self, duration: Duration) -> Self
+  where
+    Self: Sized,
+  {
+    self.layout_mut().variants.transition = Some(duration);
+    self
+  }
+
+  /* Lays out the tree in the given space, text is measured with the given fonts */
+  fn calculate(&mut self, width: u32, height: u32, unit_size: u32, text: &TextContext) {
+    let layout = self.layout();
+    if let (Ok(width), Ok(height)) = (layout.content_width(width, unit_size), layout.content_height(height, unit_size)) {
+      let intrinsic = self.measure(width, height, unit_size, text);
+      self.layout_mut().intrinsic = intrinsic;
+    }
+
+    let layout = self.layout_mut();
+    let layout_info = LayoutInfo { width, height, x: 0, y: 0, unit_size, text };
     if layout.calculate(layout_info).is_ok() {
       layout.position(layout_info);
+      layout.resolve_transforms(&Matrix::default(), &[], unit_size);
     }
   }
 
-  fn update_unit_size(&mut self, unit_size: u32) {}
+  fn update_unit_size(&mut self, _unit_size: u32) {}
+
+  /* Loads the images the element shows through the shared cache */
+  fn load_images(&mut self, _images: &mut ImageCache) -> Result<(), ImageError> { Ok(()) }
+
+  /* Whether a source changed at runtime and load_images has to run again */
+  fn images_stale(&self) -> bool { false }
+
+  /* Hands the element the application's redraw handle, to request frames when its content changes outside of an event */
+  fn connect(&mut self, _redraw: &RedrawHandle) {}
+
+  /* Size of the element's own content in pixels, given the space available inside its padding and the fonts to shape text with */
+  fn measure(&mut self, _width: u32, _height: u32, _unit_size: u32, _text: &TextContext) -> Option<(u32, u32)> { None }
+
+  /* Records what the element paints before its children, by default the background, elements with content add it after */
+  fn display(&self, list: &mut DisplayList, unit_size: u32) {
+    let layout = self.layout();
+    list.extend(layout.current_style().fill_item(layout.inner_rect(), unit_size));
+  }
+
+  /* Records what the element paints after its children, by default the border, so children never cover it */
+  fn display_over(&self, list: &mut DisplayList, unit_size: u32) {
+    let layout = self.layout();
+    list.extend(layout.current_style().border_item(layout.inner_rect(), layout.border(), unit_size));
+  }
+
+  /* Handles an input event hitting the element, returns true if it was consumed */
+  fn event(&mut self, _event: &UserEvent) -> bool { false }
 }
 
 pub struct LayoutIter<'a> {
@@ -233,7 +536,8 @@ pub struct LayoutIterMut<'a> {
 }
 
 
-impl dyn LayoutElement {
+impl<'e> dyn LayoutElement + 'e {
+  /* Every element of the tree in paint order, an element before its children and siblings in order */
   pub fn iter<'a>(&'a self) -> LayoutIter<'a> {
       LayoutIter { stack: vec![self] }
   }
@@ -241,6 +545,109 @@ impl dyn LayoutElement {
   pub fn iter_mut<'a>(&'a mut self) -> LayoutIterMut<'a> {
       LayoutIterMut { stack: vec![self] }
   }
+
+  /* Display list of the tree in paint order: every element, then its children in order, then the element's overlay */
+  pub fn display_list(&self, unit_size: u32) -> DisplayList {
+    let mut list = DisplayList::new();
+    self.display_tree(&mut list, unit_size);
+    list
+  }
+
+  fn display_tree(&self, list: &mut DisplayList, unit_size: u32) {
+    let layout = self.layout();
+    if layout.current_style().opacity <= 0.0 { return }
+    let (transform, layer) = (layout.transform_item(), layout.layer_item());
+
+    // Outside of the cached picture, transforming or fading a cache boundary doesn't record it again
+    let scopes = transform.iter().chain(&layer).count();
+    list.extend(transform);
+    list.extend(layer);
+
+    match &layout.cache {
+      Some(cache) => list.extend(cache.picture(self, unit_size, |list| self.display_subtree(list, unit_size)).map(DisplayItem::Picture)),
+      None => self.display_subtree(list, unit_size),
+    }
+
+    list.extend(std::iter::repeat_n(DisplayItem::Pop, scopes));
+  }
+
+  fn display_subtree(&self, list: &mut DisplayList, unit_size: u32) {
+    self.display(list, unit_size);
+
+    let clip = self.layout().clip_item(unit_size);
+    let clipped = clip.is_some();
+    list.extend(clip);
+    for child in &self.layout().children {
+      child.display_tree(list, unit_size);
+    }
+    if clipped { list.push(DisplayItem::Pop); }
+
+    // The border is drawn over the clipped children
+    self.display_over(list, unit_size);
+  }
+
+  /* Hits and misses of all cache boundaries in the tree */
+  pub fn cache_stats(&self) -> CacheStats {
+    self.iter()
+      .filter_map(|element| element.layout().cache())
+      .fold(CacheStats::default(), |stats, cache| stats + cache.stats())
+  }
+
+  /* Paints the tree onto the canvas through its display list, e.g. into a document */
+  pub fn paint_tree(&self, canvas: &Canvas, unit_size: u32) {
+    self.display_list(unit_size).replay(canvas);
+  }
+
+  /* Advances the style transitions of the tree to the given time */
+  pub fn animate(&mut self, now: Instant) {
+    for element in self.iter_mut() {
+      let layout = element.layout_mut();
+      layout.variants.resolve(&layout.style, now);
+    }
+  }
+
+  /* Whether a style transition of the tree is still running */
+  pub fn animating(&self) -> bool {
+    self.iter().any(|element| element.layout().variants.animating())
+  }
+
+  /*
+  Visits the topmost enabled element under the point, as painted, then the enabled elements enclosing it that
+  contain the point, until visit returns true. Returns whether it did.
+  */
+  pub fn visit_hit(&mut self, x: f32, y: f32, visit: &mut dyn FnMut(&mut dyn LayoutElement) -> bool) -> bool {
+    visit_hit(self, x, y, visit).1
+  }
+
+  /*
+  Offers a positional event to the topmost enabled element under the point, then to the elements enclosing it,
+  until one consumes it. Elements get the event in their layout coordinates, as if nothing was transformed.
+  */
+  pub fn deliver(&mut self, event: UserEvent) -> bool {
+    let Some((x, y)) = event.position() else { return false };
+    self.visit_hit(x, y, &mut |element| {
+      let (x, y) = element.layout().to_local(x, y).unwrap_or((x, y));
+      element.event(&event.at(x, y))
+    })
+  }
+}
+
+
+/* Walks the subtree in reverse paint order, returns whether an element of it was hit and whether visit returned true */
+fn visit_hit(element: &mut dyn LayoutElement, x: f32, y: f32, visit: &mut dyn FnMut(&mut dyn LayoutElement) -> bool) -> (bool, bool) {
+  let mut hit = false;
+  for child in element.layout_mut().children.iter_mut().rev() {
+    let (child_hit, done) = visit_hit(child.as_mut(), x, y, visit);
+    if done { return (true, true) }
+    if child_hit {
+      hit = true;
+      break;
+    }
+  }
+
+  let layout = element.layout();
+  if !layout.contains(x, y) || layout.variants.interaction.disabled { return (hit, false) }
+  (true, visit(element))
 }
 
 
@@ -249,7 +656,8 @@ impl<'a> Iterator for LayoutIter<'a> {
 
   fn next(&mut self) -> Option<Self::Item> {
     let layout = self.stack.pop()?;
-    self.stack.extend(layout.layout().children.iter().map(|ch| ch.as_ref()));
+    // Reversed on the stack, so the first child comes out next
+    self.stack.extend(layout.layout().children.iter().rev().map(|ch| ch.as_ref()));
     Some(layout)
   }
 }
@@ -269,12 +677,23 @@ impl<'a> Iterator for LayoutIterMut<'a> {
       children.push(child_ref);
     }
     
-    // Now extend the stack with these children
-    self.stack.extend(children);
+    // Now extend the stack with these children, reversed so they come out in order
+    self.stack.extend(children.into_iter().rev());
     
     Some(layout)
   }
 }
+fn calculate_element(element: &mut dyn LayoutElement, info: LayoutInfo) -> Result<(), LayoutError> {
+  let layout = element.layout();
+  let width = layout.content_width(info.width, info.unit_size)?;
+  let height = layout.content_height(info.height, info.unit_size)?;
+
+  let intrinsic = element.measure(width, height, info.unit_size, info.text);
+  let layout = element.layout_mut();
+  layout.intrinsic = intrinsic;
+  layout.calculate(info)
+}
+
 pub enum LayoutError {
   MultipleMaxChildren,
   DoubleSameSized,
@@ -284,22 +703,23 @@ pub enum LayoutError {
 
 
 #[derive(Clone, Copy)]
-struct LayoutInfo {
+struct LayoutInfo<'a> {
   width: u32,
   height: u32,
   x: u32,
   y: u32,
-  unit_size: u32
+  unit_size: u32,
+  text: &'a TextContext,
 }
 
-impl LayoutInfo {
+impl LayoutInfo<'_> {
 
   pub fn shrink(&self, width: u32, height: u32) -> Self {
-    LayoutInfo { width: width, height: height, x: self.x, y: self.y, unit_size: self.unit_size }
+    LayoutInfo { width: width, height: height, x: self.x, y: self.y, unit_size: self.unit_size, text: self.text }
   }
 
   pub fn shrink_frame(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
-    LayoutInfo { width: width, height: height, x: x, y: y, unit_size: self.unit_size }  
+    LayoutInfo { width: width, height: height, x: x, y: y, unit_size: self.unit_size, text: self.text }  
   }
 }
 
@@ -314,6 +734,154 @@ impl Layout {
     self.children.iter_mut().map(|ch| ch.layout_mut())
   }
 
+  /*
+  Whether the point in window coordinates lies within the frame inside the margin, as transformed when painting.
+  Parts cut off by the clip of an ancestor aren't hit.
+  */
+  pub fn contains(&self, x: f32, y: f32) -> bool {
+    let clipped = self.ancestor_clips.iter().any(|(to_ancestor, clip)| {
+      let point = to_ancestor.map_point((x, y));
+      !rrect_contains(clip, point.x, point.y)
+    });
+    if clipped { return false }
+
+    let Some((x, y)) = self.to_local(x, y) else { return false };
+    let (left, top) = (self.computed.inner_pos.0 as f32, self.computed.inner_pos.1 as f32);
+    let (width, height) = (self.computed.inner_dim.0 as f32, self.computed.inner_dim.1 as f32);
+    x >= left && x < left + width && y >= top && y < top + height
+  }
+
+  pub fn border(&self) -> [f32; 4] {
+    self.border
+  }
+
+  /* Frame including the margin, everything the element paints lies within it */
+  pub fn outer_rect(&self) -> Rect {
+    let (x, y) = self.computed.outer_pos;
+    let (width, height) = self.computed.outer_dim;
+    Rect::from_xywh(x as f32, y as f32, width as f32, height as f32)
+  }
+
+  /* Frame inside the margin, where background and border are drawn */
+  pub fn inner_rect(&self) -> Rect {
+    let (x, y) = self.computed.inner_pos;
+    let (width, height) = self.computed.inner_dim;
+    Rect::from_xywh(x as f32, y as f32, width as f32, height as f32)
+  }
+
+  /* Point in window coordinates in the layout coordinates of the element, undoing its and its ancestors' transforms */
+  pub fn to_local(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+    let point = self.window_matrix.invert()?.map_point((x, y));
+    Some((point.x, point.y))
+  }
+
+  /* Area the element covers in the window when painted, its transforms applied */
+  pub fn window_rect(&self) -> Rect {
+    self.window_matrix.map_rect(self.outer_rect()).0
+  }
+
+  pub fn clip(&self) -> Clip {
+    self.clip
+  }
+
+  pub fn set_clip(&mut self, clip: Clip) {
+    self.clip = clip;
+    self.damage();
+  }
+
+  pub fn transform(&self) -> Option<Transform> {
+    self.transform
+  }
+
+  /* Takes effect with the next layout calculation */
+  pub fn set_transform(&mut self, transform: Option<Transform>) {
+    self.transform = transform;
+    self.damaged = true; // the content stays the same, cached pictures remain valid
+  }
+
+  /* Matrix from the layout coordinates of the element into the window */
+  pub fn window_matrix(&self) -> Matrix {
+    self.window_matrix
+  }
+
+  fn resolve_transforms(&mut self, parent: &Matrix, clips: &[(Matrix, RRect)], unit_size: u32) {
+    self.local_matrix = self.transform.map_or(Matrix::default(), |transform| transform.matrix(self.inner_rect(), unit_size));
+    self.window_matrix = Matrix::concat(parent, &self.local_matrix);
+    self.ancestor_clips = clips.to_vec();
+
+    // The element's own clip only applies to its children
+    let mut clips = clips.to_vec();
+    if let (Some(radii), Some(to_local)) = (self.clip_radii(unit_size), self.window_matrix.invert()) {
+      clips.push((to_local, rounded_rect(self.inner_rect(), radii, [0.0; 4])));
+    }
+
+    let window = self.window_matrix;
+    for layout in self.layouts_mut() {
+      layout.resolve_transforms(&window, &clips, unit_size);
+    }
+  }
+
+  /* Display item opening the element's transform, None without one */
+  pub fn transform_item(&self) -> Option<DisplayItem> {
+    self.transform?;
+    self.local_matrix.to_affine().map(DisplayItem::PushTransform)
+  }
+
+  /* Display item fading the element together with its children, None while it is opaque */
+  pub fn layer_item(&self) -> Option<DisplayItem> {
+    let opacity = self.current_style().opacity;
+    (opacity < 1.0).then_some(DisplayItem::PushLayer { opacity })
+  }
+
+  /* Display item opening the clip of the children, None if they aren't clipped */
+  pub fn clip_item(&self, unit_size: u32) -> Option<DisplayItem> {
+    let radii = self.clip_radii(unit_size)?;
+    Some(DisplayItem::PushClip { bounds: self.inner_rect().into(), radii })
+  }
+
+  /* Corner radii of the clip around the children in pixels, None if they aren't clipped */
+  fn clip_radii(&self, unit_size: u32) -> Option<[f32; 4]> {
+    match self.clip {
+      Clip::None => None,
+      Clip::Rect => Some([0.0; 4]),
+      Clip::Rounded => Some(self.current_style().radii(unit_size)),
+    }
+  }
+
+  /* Marks the element to be repainted in the next frame, for changes of its content */
+  pub fn damage(&mut self) {
+    self.damaged = true;
+    self.revision += 1;
+  }
+
+  /* Number of times the element reported damage */
+  pub fn revision(&self) -> u64 {
+    self.revision
+  }
+
+  /* Makes the element a cache boundary, painting it and its children from a picture while none of them changes */
+  pub fn set_cached(&mut self, cached: bool) {
+    self.cache = cached.then(PictureCache::new);
+  }
+
+  pub fn cache(&self) -> Option<&PictureCache> {
+    self.cache.as_ref()
+  }
+
+  /* Whether the element was marked since the last call, clearing the mark */
+  pub fn take_damage(&mut self) -> bool {
+    std::mem::take(&mut self.damaged)
+  }
+
+  pub fn children(&self) -> impl Iterator<Item = &dyn LayoutElement> {
+    self.children.iter().map(|child| child.as_ref())
+  }
+
+  /* Style of the last resolved frame, including state variants and transitions */
+  pub fn current_style(&self) -> Style {
+    self.variants.current().copied().unwrap_or(self.style)
+  }
+
   pub fn horizontal(&self) -> Size {
     if self.hsize != Size::Same { self.hsize } else {self.vsize }
   }
@@ -407,17 +975,19 @@ impl Layout {
     };
 
     // 5) compute own size
+    let (intrinsic_width, intrinsic_height) = self.intrinsic.unwrap_or_default();
+    let (child_width, child_height) = (max(child_width, intrinsic_width), max(child_height, intrinsic_height));
     self.computed.core_dim = (child_width, child_height);
     if self.horizontal() == Size::Content && child_width < self.computed.content_dim.0 {
       self.computed.content_dim.0 = child_width;
       self.computed.inner_dim.0 = child_width + ((self.padding[0] + self.padding[1]) * info.unit_size as f32) as u32;
-      self.computed.outer_dim.0 = child_width + ((self.margin[0] + self.margin[1]) * info.unit_size as f32) as u32;
+      self.computed.outer_dim.0 = self.computed.inner_dim.0 + ((self.margin[0] + self.margin[1]) * info.unit_size as f32) as u32;
     }
 
     if self.vertical() == Size::Content && child_height < self.computed.content_dim.1 {
       self.computed.content_dim.1 = child_height;
       self.computed.inner_dim.1 = child_height + ((self.padding[2] + self.padding[3]) * info.unit_size as f32) as u32;
-      self.computed.outer_dim.1 = child_height + ((self.margin[2] + self.margin[3]) * info.unit_size as f32) as u32;
+      self.computed.outer_dim.1 = self.computed.inner_dim.1 + ((self.margin[2] + self.margin[3]) * info.unit_size as f32) as u32;
     }
 
     Ok(())
@@ -433,8 +1003,8 @@ impl Layout {
     let mut content = Vec::new();
     let mut max_child = None;
 
-    for child in self.layouts_mut() {
-      match child.horizontal() {
+    for child in self.children.iter_mut().map(|ch| ch.as_mut()) {
+      match child.layout().horizontal() {
         Size::Relative(_) | Size::Unit(_) => fixed.push(child),
         Size::Content           => content.push(child),
         Size::Max               => {
@@ -451,13 +1021,13 @@ impl Layout {
     
     // 1) layout fixed and content-size
     for ch in &mut fixed {
-      ch.calculate(info)?;
-      rem = rem - ch.computed.outer_dim.0 as i32;
+      calculate_element(&mut **ch, info)?;
+      rem = rem - ch.layout().computed.outer_dim.0 as i32;
     }
 
     // 2) remaining max-sized child
     if let Some(ch) = max_child {
-      ch.calculate(info.shrink(max(rem, 0) as u32, height))?;
+      calculate_element(ch, info.shrink(max(rem, 0) as u32, height))?;
     }    
 
     Ok(())
@@ -475,8 +1045,8 @@ impl Layout {
     let mut content = Vec::new();
     let mut max_child = None;
     
-    for child in self.layouts_mut() {
-      match child.vertical() {
+    for child in self.children.iter_mut().map(|ch| ch.as_mut()) {
+      match child.layout().vertical() {
         Size::Relative(_) | Size::Unit(_) => fixed.push(child),
         Size::Content               => content.push(child),
         Size::Max => {
@@ -494,13 +1064,13 @@ impl Layout {
     
     // 1) layout fixed and content-size
     for ch in &mut fixed {
-      ch.calculate(info)?;
-      rem = rem - ch.computed.outer_dim.1 as i32;
+      calculate_element(&mut **ch, info)?;
+      rem = rem - ch.layout().computed.outer_dim.1 as i32;
     }
 
     // 3) remaining max-sized child
     if let Some(ch) = max_child {
-      ch.calculate(info.shrink(width, max(rem, 0) as u32))?;
+      calculate_element(ch, info.shrink(width, max(rem, 0) as u32))?;
     }    
 
     Ok(())
@@ -680,7 +1250,7 @@ mod tests {
               TestElement::with_size(30.0, 15.0).boxed(),
           ]);
           
-      root.calculate(1000, 1000, 10);
+      root.calculate(1000, 1000, 10, &TextContext::default());
       
       let layout = root.layout();
       assert_eq!(layout.computed.outer_dim.0, 600); // 10*10 + 20*10 + 30*10
@@ -702,7 +1272,7 @@ mod tests {
               TestElement::with_size(15.0, 30.0).boxed(),
           ]);
           
-      root.calculate(1000, 1000, 10);
+      root.calculate(1000, 1000, 10, &TextContext::default());
       
       let layout = root.layout();
       assert_eq!(layout.computed.outer_dim.1, 450); // 5*10 + 10*10 + 30*10
@@ -718,7 +1288,7 @@ mod tests {
               TestElement::with_size(30.0, 15.0).boxed(),
           ]);
           
-      root.calculate(1000, 100, 10);
+      root.calculate(1000, 100, 10, &TextContext::default());
       
       let layout = root.layout();
       let children: Vec<_> = layout.layouts().collect();
@@ -743,7 +1313,7 @@ mod tests {
               TestElement::with_size(50.0, 30.0).boxed(),
           ]);
           
-      root.calculate(2000, 2000, 10);
+      root.calculate(2000, 2000, 10, &TextContext::default());
       
       let layout = root.layout();
       assert_eq!(layout.computed.outer_dim.0, 1000); // 100*10
@@ -773,7 +1343,7 @@ mod tests {
           TestElement::with_size(10.0, 10.0).boxed(),
       ]);
       
-      root.calculate(1000, 1000, 10);
+      root.calculate(1000, 1000, 10, &TextContext::default());
       
       let layout = root.layout();
       let children: Vec<_> = layout.layouts().collect();
@@ -793,7 +1363,7 @@ mod tests {
               TestElement::with_size(30.0, 10.0).boxed(),
           ]);
           
-      root.calculate(1000, 1000, 10);
+      root.calculate(1000, 1000, 10, &TextContext::default());
       
       let layout = root.layout();
       assert_eq!(layout.computed.outer_dim.0, 400); // 10*10 + 30*10
@@ -810,7 +1380,7 @@ mod tests {
               TestElement::new().width(Size::Relative(0.7)).height(Size::Relative(0.8)).boxed(),
           ]);
           
-      root.calculate(1000, 1000, 10);
+      root.calculate(1000, 1000, 10, &TextContext::default());
       
       let layout = root.layout();
       let children: Vec<_> = layout.layouts().collect();
@@ -830,7 +1400,7 @@ mod tests {
               TestElement::new().width(Size::Same).height(Size::Unit(30.0)).boxed(),
           ]);
           
-      root.calculate(1000, 1000, 10);
+      root.calculate(1000, 1000, 10, &TextContext::default());
       
       let layout = root.layout();
       let children: Vec<_> = layout.layouts().collect();
@@ -840,13 +1410,157 @@ mod tests {
       assert_eq!(children[0].computed.outer_dim.1, 300);
   }
   
+  struct MeasuredElement {
+    layout: Layout,
+    size: (u32, u32),
+  }
+
+  impl LayoutElement for MeasuredElement {
+    fn layout(&self) -> &Layout {
+      &self.layout
+    }
+
+    fn layout_mut(&mut self) -> &mut Layout {
+      &mut self.layout
+    }
+
+    fn measure(&mut self, _width: u32, _height: u32, _unit_size: u32, _text: &TextContext) -> Option<(u32, u32)> {
+      Some(self.size)
+    }
+  }
+
+  #[test]
+  fn test_measured_content() {
+      let mut root = TestElement::new()
+          .horizontal(vec![
+              MeasuredElement { layout: Layout::default(), size: (120, 30) }.padding(1.0).boxed(),
+              TestElement::with_size(10.0, 5.0).boxed(),
+          ]);
+
+      root.calculate(1000, 1000, 10, &TextContext::default());
+
+      let layout = root.layout();
+      let children: Vec<_> = layout.layouts().collect();
+      assert_eq!(children[0].computed.content_dim, (120, 30));
+      assert_eq!(children[0].computed.outer_dim, (140, 50));
+      assert_eq!(layout.computed.outer_dim.0, 240);
+  }
+
+  struct PaintedElement {
+    layout: Layout,
+    name: &'static str,
+    log: std::rc::Rc<std::cell::RefCell<Vec<String>>>,
+  }
+
+  impl LayoutElement for PaintedElement {
+    fn layout(&self) -> &Layout {
+      &self.layout
+    }
+
+    fn layout_mut(&mut self) -> &mut Layout {
+      &mut self.layout
+    }
+
+    fn display(&self, _list: &mut DisplayList, _unit_size: u32) {
+      self.log.borrow_mut().push(self.name.to_string());
+    }
+
+    fn display_over(&self, _list: &mut DisplayList, _unit_size: u32) {
+      self.log.borrow_mut().push(format!("{}/over", self.name));
+    }
+  }
+
+  #[test]
+  fn test_paint_order() {
+      let log = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+      let element = |name| PaintedElement { layout: Layout::default(), name, log: log.clone() };
+
+      let root = element("root")
+          .vertical(vec![
+              element("a").horizontal(vec![element("a1").boxed()]).boxed(),
+              element("b").boxed(),
+          ]);
+
+      (&root as &dyn LayoutElement).display_list(10);
+
+      assert_eq!(*log.borrow(), ["root", "a", "a1", "a1/over", "a/over", "b", "b/over", "root/over"]);
+  }
+
+  #[test]
+  fn test_opacity_fades_children() {
+      let root = TestElement::new()
+          .background(Color::RED)
+          .opacity(0.5)
+          .horizontal(vec![TestElement::with_size(1.0, 1.0).background(Color::BLUE).boxed()]);
+      let mut root: Box<dyn LayoutElement> = root.boxed();
+      root.calculate(100, 100, 10, &TextContext::default());
+      root.animate(Instant::now());
+
+      let items = root.display_list(10).items().to_vec();
+      assert_eq!(items.first(), Some(&DisplayItem::PushLayer { opacity: 0.5 }));
+      assert_eq!(items.len(), 4);
+      assert_eq!(items.last(), Some(&DisplayItem::Pop));
+
+      // Fully transparent subtrees are skipped
+      root.layout_mut().style.opacity = 0.0;
+      root.animate(Instant::now());
+      assert!(root.display_list(10).is_empty());
+  }
+
+  #[test]
+  fn test_transform() {
+      let mut root = TestElement::new()
+          .clip(Clip::Rect)
+          .horizontal(vec![
+              TestElement::with_size(10.0, 10.0).transform(Transform::new().translate(5.0, 0.0).scale(2.0)).boxed(),
+          ]);
+
+      root.calculate(1000, 1000, 10, &TextContext::default());
+
+      // Scaled around the centre (50, 50), then moved right by 5 units
+      let child = root.layout().layouts().next().unwrap();
+      assert_eq!(child.window_rect(), Rect::new(0.0, -50.0, 200.0, 150.0));
+      assert_eq!(child.to_local(190.0, 140.0), Some((95.0, 95.0)));
+      assert!(child.contains(90.0, 90.0));
+      assert!(!child.contains(210.0, 0.0));
+      // Inside the scaled child, but cut off by the clip of the root
+      assert!(!child.contains(190.0, 140.0));
+
+      let list = (&root as &dyn LayoutElement).display_list(10);
+      assert_eq!(list.items(), &[
+          DisplayItem::PushClip { bounds: Rect::from_wh(100.0, 100.0).into(), radii: [0.0; 4] },
+          DisplayItem::PushTransform([2.0, 0.0, 0.0, 2.0, 0.0, -50.0]),
+          DisplayItem::Pop,
+          DisplayItem::Pop,
+      ]);
+  }
+
+  #[test]
+  fn test_clipped_hit() {
+      let mut root = TestElement::new()
+          .clip(Clip::Rounded)
+          .rounded(2.0)
+          .horizontal(vec![
+              TestElement::new().horizontal(vec![TestElement::with_size(10.0, 10.0).boxed()]).boxed(),
+          ]);
+
+      root.calculate(1000, 1000, 10, &TextContext::default());
+
+      // The clip of the root reaches the grandchild, its rounded corners included
+      let grandchild = (&root as &dyn LayoutElement).iter().nth(2).unwrap().layout();
+      assert!(grandchild.contains(50.0, 50.0));
+      assert!(grandchild.contains(99.0, 50.0));
+      assert!(!grandchild.contains(2.0, 2.0));
+      assert!(root.layout().contains(2.0, 2.0));
+  }
+
   #[test]
   fn test_empty_layout() {
       let mut root = TestElement::new()
           .width(Size::Unit(50.0))
           .height(Size::Unit(50.0));
           
-      root.calculate(1000, 1000, 10);
+      root.calculate(1000, 1000, 10, &TextContext::default());
       
       let layout = root.layout();
       assert_eq!(layout.computed.outer_dim.0, 500);
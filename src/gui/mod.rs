@@ -1,4 +1,14 @@
+pub mod cache;
+pub mod canvas;
 pub mod container;
 
+pub mod fonts;
+pub mod image;
 pub mod layout;
+pub mod paragraph;
+pub mod rich_text;
+pub mod style;
+#[cfg(feature = "svg")]
+pub mod svg;
 pub mod text;
+pub mod theme;
@@ -0,0 +1,231 @@
+use std::rc::Rc;
+
+use skia_safe::{font_style::{Slant, Weight}, textlayout::{Paragraph, ParagraphBuilder, TextAlign}, Color};
+
+use super::{layout::{Alignment, Layout, LayoutElement}, text::{align, base_direction, FontSpec, TextContext}};
+use crate::objects::{DisplayItem, DisplayList};
+
+
+/* Multi-line text, wrapped to the width available to the element */
+pub struct ParagraphElement {
+  content : String,
+  layout: Layout,
+
+  spec : FontSpec,
+  line_height : Option<f32>, // multiple of the font size
+  max_lines : Option<usize>,
+  ellipsis : Option<String>,
+
+  halign : Alignment,
+  valign : Alignment,
+
+  paragraph : Option<Rc<Paragraph>>, // shared with the display items painting it
+  built_with : Option<(Color, f32)>, // colour and text scale the paragraph was built with
+  laid_out : Option<u32>,            // width the paragraph was laid out at
+}
+
+
+impl LayoutElement for ParagraphElement {
+  fn layout(&self) -> &Layout {
+    &self.layout
+  }
+
+  fn layout_mut(&mut self) -> &mut Layout {
+    &mut self.layout
+  }
+
+  fn font_families(&self) -> Vec<&str> {
+    self.spec.family.as_deref().into_iter().collect()
+  }
+
+  fn update_unit_size(&mut self, _unit_size: u32) {
+    self.invalidate();
+  }
+
+  fn measure(&mut self, width: u32, _height: u32, _unit_size: u32, text: &TextContext) -> Option<(u32, u32)> {
+    let color = self.layout.variants.target(&self.layout.style).color;
+    // A paragraph display items still hold is built anew instead of being laid out again under them
+    let shared = self.paragraph.as_mut().is_some_and(|paragraph| Rc::get_mut(paragraph).is_none());
+    if self.built_with != Some((color, text.scale)) || (self.laid_out != Some(width) && shared) {
+      self.paragraph = Some(Rc::new(self.build(color, text)));
+      self.built_with = Some((color, text.scale));
+      self.laid_out = None;
+    }
+
+    let paragraph = self.paragraph.as_mut()?;
+    if self.laid_out != Some(width) {
+      Rc::get_mut(paragraph)?.layout(width as f32);
+      self.laid_out = Some(width);
+    }
+
+    Some((paragraph.longest_line().ceil() as u32, paragraph.height().ceil() as u32))
+  }
+
+  fn display(&self, list: &mut DisplayList, unit_size: u32) {
+    list.extend(self.layout.current_style().fill_item(self.layout.inner_rect(), unit_size));
+
+    let (Some(paragraph), Some(laid_out), Some((color, scale))) = (&self.paragraph, self.laid_out, self.built_with) else { return };
+
+    let computed = &self.layout.computed;
+
+    // Lines are aligned within the width the paragraph was laid out at, which can differ from the content box
+    let x = match self.halign {
+      Alignment::Even => 0.0,
+      halign => align(computed.content_dim.0 as f32, laid_out as f32, halign),
+    };
+    let y = align(computed.content_dim.1 as f32, paragraph.height(), self.valign);
+
+    list.push(DisplayItem::Text {
+      text: self.content.clone(),
+      origin: (computed.content_pos.0 as f32 + x, computed.content_pos.1 as f32 + y),
+      font: self.spec.clone(),
+      size: self.spec.size * scale,
+      color: color.into(),
+      paragraph: paragraph.clone(),
+    });
+  }
+}
+
+impl ParagraphElement {
+
+  pub fn new<T: Into<String>>(content : T) -> Self {
+    ParagraphElement {
+      content: content.into(),
+      layout: Default::default(),
+      spec: FontSpec::default(),
+      line_height: None,
+      max_lines: None,
+      ellipsis: None,
+      halign: Alignment::Start,
+      valign: Alignment::Start,
+      paragraph: None,
+      built_with: None,
+      laid_out: None,
+    }
+    .class("text")
+  }
+
+  pub fn content(&self) -> &str {
+    &self.content
+  }
+
+  pub fn set_content<T: Into<String>>(&mut self, content : T) {
+    self.content = content.into();
+    self.invalidate();
+  }
+
+  pub fn font_family<T: Into<String>>(mut self, family : T) -> Self {
+    self.spec.family = Some(family.into());
+    self.invalidate();
+    self
+  }
+
+  /* Families tried in order for characters the main family has no glyph for */
+  pub fn font_fallback<T: Into<String>>(mut self, families : impl IntoIterator<Item = T>) -> Self {
+    self.spec.fallback = families.into_iter().map(Into::into).collect();
+    self.invalidate();
+    self
+  }
+
+  pub fn font_size(mut self, size : f32) -> Self {
+    self.spec.size = size;
+    self.invalidate();
+    self
+  }
+
+  pub fn weight(mut self, weight : Weight) -> Self {
+    self.spec.weight = weight;
+    self.invalidate();
+    self
+  }
+
+  pub fn italic(mut self) -> Self {
+    self.spec.slant = Slant::Italic;
+    self.invalidate();
+    self
+  }
+
+  /* Line height as a multiple of the font size */
+  pub fn line_height(mut self, height : f32) -> Self {
+    self.line_height = Some(height);
+    self.invalidate();
+    self
+  }
+
+  /* Truncates the paragraph after the given number of lines, ending the last one with an ellipsis */
+  pub fn max_lines(mut self, lines : usize) -> Self {
+    self.max_lines = Some(lines);
+    self.ellipsis.get_or_insert_with(|| "\u{2026}".into());
+    self.invalidate();
+    self
+  }
+
+  pub fn ellipsis<T: Into<String>>(mut self, ellipsis : T) -> Self {
+    self.ellipsis = Some(ellipsis.into());
+    self.invalidate();
+    self
+  }
+
+  /* Alignment of the lines (Even justifies them) and of the whole paragraph inside the content box */
+  pub fn text_align(mut self, halign : Alignment, valign : Alignment) -> Self {
+    self.halign = halign;
+    self.valign = valign;
+    self.invalidate();
+    self
+  }
+
+  fn invalidate(&mut self) {
+    self.paragraph = None;
+    self.built_with = None;
+    self.laid_out = None;
+    self.layout.damage();
+  }
+
+  fn build(&self, color : Color, text : &TextContext) -> Paragraph {
+    let mut text_style = self.spec.text_style(color, text.scale);
+    if let Some(height) = self.line_height {
+      text_style.set_height(height);
+      text_style.set_height_override(true);
+    }
+
+    let mut style = text.paragraph_style();
+    style.set_text_style(&text_style);
+    style.set_text_align(match self.halign {
+      Alignment::Start => TextAlign::Start,
+      Alignment::End => TextAlign::End,
+      Alignment::Center => TextAlign::Center,
+      Alignment::Even => TextAlign::Justify,
+    });
+    style.set_text_direction(base_direction(&self.content));
+    style.set_max_lines(self.max_lines);
+    if let Some(ellipsis) = &self.ellipsis {
+      style.set_ellipsis(ellipsis);
+    }
+
+    let mut builder = ParagraphBuilder::new(&style, text.collection.clone());
+    builder.push_style(&text_style);
+    builder.add_text(&self.content);
+    builder.build()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_relayout_while_displayed() {
+    let text = TextContext::default();
+    let mut element = ParagraphElement::new("Some words to wrap");
+    element.measure(200, 100, 10, &text).unwrap();
+
+    let list = (&element as &dyn LayoutElement).display_list(10);
+    let Some(DisplayItem::Text { paragraph, .. }) = list.items().last() else { panic!("no text item") };
+
+    // The list still paints the paragraph at its width, another width builds a new one
+    element.measure(50, 100, 10, &text).unwrap();
+    assert!(!Rc::ptr_eq(paragraph, element.paragraph.as_ref().unwrap()));
+    assert_eq!(paragraph.max_width(), 200.0);
+  }
+}
@@ -0,0 +1,284 @@
+use std::{ops::Range, rc::Rc};
+
+use skia_safe::{font_style::{Slant, Weight}, textlayout::{Paragraph, ParagraphBuilder, RectHeightStyle, RectWidthStyle, TextDecoration, TextStyle}, Color, Contains, Paint, Point};
+
+use crate::{objects::{DisplayItem, DisplayList}, user_event::UserEvent};
+
+use super::{layout::{Layout, LayoutElement}, text::{base_direction, FontSpec, TextContext}};
+
+
+/* Run of text with its own style, laid out as part of a RichText paragraph */
+pub struct Span {
+  text : String,
+
+  family : Option<String>,
+  size : Option<f32>,
+  weight : Option<Weight>,
+  slant : Option<Slant>,
+  color : Option<Color>,
+  background : Option<Color>,
+  underline : bool,
+
+  on_click : Option<Box<dyn FnMut(&str)>>,
+}
+
+
+impl Span {
+
+  pub fn new<T: Into<String>>(text : T) -> Self {
+    Span {
+      text: text.into(),
+      family: None,
+      size: None,
+      weight: None,
+      slant: None,
+      color: None,
+      background: None,
+      underline: false,
+      on_click: None,
+    }
+  }
+
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+
+  pub fn font_family<T: Into<String>>(mut self, family : T) -> Self {
+    self.family = Some(family.into());
+    self
+  }
+
+  pub fn font_size(mut self, size : f32) -> Self {
+    self.size = Some(size);
+    self
+  }
+
+  pub fn weight(mut self, weight : Weight) -> Self {
+    self.weight = Some(weight);
+    self
+  }
+
+  pub fn bold(self) -> Self {
+    self.weight(Weight::BOLD)
+  }
+
+  pub fn italic(mut self) -> Self {
+    self.slant = Some(Slant::Italic);
+    self
+  }
+
+  pub fn color(mut self, color : Color) -> Self {
+    self.color = Some(color);
+    self
+  }
+
+  pub fn background(mut self, color : Color) -> Self {
+    self.background = Some(color);
+    self
+  }
+
+  pub fn underline(mut self) -> Self {
+    self.underline = true;
+    self
+  }
+
+  /* Makes the span clickable, the handler receives the text of the span */
+  pub fn on_click(mut self, handler : impl FnMut(&str) + 'static) -> Self {
+    self.on_click = Some(Box::new(handler));
+    self
+  }
+
+  /* Underlined, clickable span */
+  pub fn link(self, handler : impl FnMut(&str) + 'static) -> Self {
+    self.underline().on_click(handler)
+  }
+
+  fn text_style(&self, base : &TextStyle, scale : f32) -> TextStyle {
+    let mut style = base.clone();
+
+    if let Some(family) = &self.family {
+      // Keep the fallback families of the base font behind the span family
+      let mut families: Vec<String> = style.font_families().iter().map(str::to_string).collect();
+      families.insert(0, family.clone());
+      style.set_font_families(&families);
+    }
+    if let Some(size) = self.size { style.set_font_size(size * scale); }
+    if let Some(color) = self.color { style.set_color(color); }
+
+    if self.weight.is_some() || self.slant.is_some() {
+      let font_style = style.font_style();
+      style.set_font_style(skia_safe::FontStyle::new(
+        self.weight.unwrap_or(font_style.weight()),
+        font_style.width(),
+        self.slant.unwrap_or(font_style.slant()),
+      ));
+    }
+
+    if let Some(background) = self.background {
+      let mut paint = Paint::default();
+      paint.set_color(background);
+      style.set_background_paint(&paint);
+    }
+
+    if self.underline {
+      style.set_decoration_type(TextDecoration::UNDERLINE);
+      style.set_decoration_color(style.color());
+    }
+
+    style
+  }
+}
+
+
+/* Paragraph composed of differently styled spans, spans with a click handler can be hit by the cursor */
+pub struct RichText {
+  spans : Vec<Span>,
+  ranges : Vec<Range<usize>>, // UTF-16 range of every span within the paragraph text
+  layout : Layout,
+
+  spec : FontSpec,
+
+  paragraph : Option<Rc<Paragraph>>, // shared with the display items painting it
+  built_with : Option<(Color, f32)>, // base colour and text scale the paragraph was built with
+  laid_out : Option<u32>,
+}
+
+
+impl LayoutElement for RichText {
+  fn layout(&self) -> &Layout {
+    &self.layout
+  }
+
+  fn layout_mut(&mut self) -> &mut Layout {
+    &mut self.layout
+  }
+
+  fn font_families(&self) -> Vec<&str> {
+    self.spec.family.iter().chain(self.spans.iter().filter_map(|span| span.family.as_ref())).map(String::as_str).collect()
+  }
+
+  fn update_unit_size(&mut self, _unit_size: u32) {
+    self.invalidate();
+  }
+
+  fn measure(&mut self, width: u32, _height: u32, _unit_size: u32, text: &TextContext) -> Option<(u32, u32)> {
+    let color = self.layout.variants.target(&self.layout.style).color;
+    // A paragraph display items still hold is built anew instead of being laid out again under them
+    let shared = self.paragraph.as_mut().is_some_and(|paragraph| Rc::get_mut(paragraph).is_none());
+    if self.built_with != Some((color, text.scale)) || (self.laid_out != Some(width) && shared) {
+      self.paragraph = Some(Rc::new(self.build(color, text)));
+      self.built_with = Some((color, text.scale));
+      self.laid_out = None;
+    }
+
+    let paragraph = self.paragraph.as_mut()?;
+    if self.laid_out != Some(width) {
+      Rc::get_mut(paragraph)?.layout(width as f32);
+      self.laid_out = Some(width);
+    }
+
+    Some((paragraph.longest_line().ceil() as u32, paragraph.height().ceil() as u32))
+  }
+
+  fn display(&self, list: &mut DisplayList, unit_size: u32) {
+    list.extend(self.layout.current_style().fill_item(self.layout.inner_rect(), unit_size));
+
+    let (Some(paragraph), Some((color, scale))) = (&self.paragraph, self.built_with) else { return };
+    let (x, y) = self.layout.computed.content_pos;
+
+    list.push(DisplayItem::Text {
+      text: self.spans.iter().map(Span::text).collect(),
+      origin: (x as f32, y as f32),
+      font: self.spec.clone(),
+      size: self.spec.size * scale,
+      color: color.into(),
+      paragraph: paragraph.clone(),
+    });
+  }
+
+  fn event(&mut self, event: &UserEvent) -> bool {
+    let UserEvent::Click(x, y) = *event else { return false };
+    let (left, top) = self.layout.computed.content_pos;
+
+    let Some(index) = self.span_at(x - left as f32, y - top as f32) else { return false };
+    let span = &mut self.spans[index];
+    match &mut span.on_click {
+      Some(handler) => {
+        handler(&span.text);
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+impl RichText {
+
+  pub fn new(spans : impl IntoIterator<Item = Span>) -> Self {
+    let spans: Vec<Span> = spans.into_iter().collect();
+
+    let mut ranges = Vec::with_capacity(spans.len());
+    let mut start = 0;
+    for span in &spans {
+      let end = start + span.text.encode_utf16().count();
+      ranges.push(start..end);
+      start = end;
+    }
+
+    RichText {
+      spans,
+      ranges,
+      layout: Default::default(),
+      spec: FontSpec::default(),
+      paragraph: None,
+      built_with: None,
+      laid_out: None,
+    }
+    .class("text")
+  }
+
+  pub fn spans(&self) -> &[Span] {
+    &self.spans
+  }
+
+  /* Base font of all spans, spans override parts of it */
+  pub fn font(mut self, spec : FontSpec) -> Self {
+    self.spec = spec;
+    self.invalidate();
+    self
+  }
+
+  /* Index of the span under the point, relative to the top left corner of the content box */
+  pub fn span_at(&self, x : f32, y : f32) -> Option<usize> {
+    let paragraph = self.paragraph.as_ref()?;
+
+    self.ranges.iter().position(|range| {
+      paragraph.get_rects_for_range(range.clone(), RectHeightStyle::Max, RectWidthStyle::Tight)
+        .iter()
+        .any(|text_box| text_box.rect.contains(Point::new(x, y)))
+    })
+  }
+
+  fn invalidate(&mut self) {
+    self.paragraph = None;
+    self.built_with = None;
+    self.laid_out = None;
+    self.layout.damage();
+  }
+
+  fn build(&self, color : Color, text : &TextContext) -> Paragraph {
+    let base = self.spec.text_style(color, text.scale);
+
+    let mut style = text.paragraph_style();
+    style.set_text_style(&base);
+    style.set_text_direction(base_direction(&self.spans.iter().map(Span::text).collect::<String>()));
+
+    let mut builder = ParagraphBuilder::new(&style, text.collection.clone());
+    for span in &self.spans {
+      builder.push_style(&span.text_style(&base, text.scale));
+      builder.add_text(&span.text);
+      builder.pop();
+    }
+    builder.build()
+  }
+}
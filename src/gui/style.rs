@@ -0,0 +1,364 @@
+use std::time::{Duration, Instant};
+
+use skia_safe::{Color, RRect, Rect, Vector};
+
+use crate::objects::{self, DisplayItem, Quad2D};
+
+use super::{layout::Axis, theme::Properties};
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Background {
+  None,
+  Color(Color),
+  Gradient { from: Color, to: Color, axis: Axis }, // linear gradient along the axis
+}
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Style {
+  pub color: Color, // foreground, e.g. text
+  pub background: Background,
+  pub border_color: Option<Color>,
+
+  pub radius: [f32; 4], // top-left, top-right, bottom-right, bottom-left (in units)
+  pub opacity: f32,
+}
+
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+          color: Color::BLACK,
+          background: Background::None,
+          border_color: None,
+          radius: Default::default(),
+          opacity: 1.0,
+        }
+    }
+}
+
+
+impl Style {
+
+  pub fn new() -> Self { Default::default() }
+
+  pub fn color(mut self, color: Color) -> Self {
+    self.color = color;
+    self
+  }
+
+  pub fn background(mut self, color: Color) -> Self {
+    self.background = Background::Color(color);
+    self
+  }
+
+  pub fn gradient(mut self, from: Color, to: Color, axis: Axis) -> Self {
+    self.background = Background::Gradient { from, to, axis };
+    self
+  }
+
+  pub fn border_color(mut self, color: Color) -> Self {
+    self.border_color = Some(color);
+    self
+  }
+
+  pub fn rounded(self, radius: f32) -> Self {
+    self.rounded_all(radius, radius, radius, radius)
+  }
+
+  pub fn rounded_all(mut self, top_left: f32, top_right: f32, bottom_right: f32, bottom_left: f32) -> Self {
+    self.radius = [top_left, top_right, bottom_right, bottom_left];
+    self
+  }
+
+  pub fn opacity(mut self, opacity: f32) -> Self {
+    self.opacity = opacity.clamp(0.0, 1.0);
+    self
+  }
+
+  /* Interpolates between two styles, t in [0, 1] */
+  pub fn lerp(&self, to: &Style, t: f32) -> Style {
+    let background = match (self.background, to.background) {
+      (Background::Color(a), Background::Color(b)) => Background::Color(mix(a, b, t)),
+      (Background::None, Background::Color(b)) => Background::Color(mix(b.with_a(0), b, t)),
+      (Background::Color(a), Background::None) => Background::Color(mix(a, a.with_a(0), t)),
+      (Background::Gradient { from: a0, to: a1, .. }, Background::Gradient { from: b0, to: b1, axis }) => {
+        Background::Gradient { from: mix(a0, b0, t), to: mix(a1, b1, t), axis }
+      }
+      (from, to) => if t < 0.5 { from } else { to },
+    };
+
+    let border_color = match (self.border_color, to.border_color) {
+      (Some(a), Some(b)) => Some(mix(a, b, t)),
+      (None, Some(b)) => Some(mix(b.with_a(0), b, t)),
+      (Some(a), None) => Some(mix(a, a.with_a(0), t)),
+      (None, None) => None,
+    };
+
+    let mut radius = self.radius;
+    for (r, target) in radius.iter_mut().zip(to.radius) {
+      *r += (target - *r) * t;
+    }
+
+    Style {
+      color: mix(self.color, to.color, t),
+      background,
+      border_color,
+      radius,
+      opacity: self.opacity + (to.opacity - self.opacity) * t,
+    }
+  }
+
+  /* Corner radii in pixels, clockwise starting at the top left corner */
+  pub(crate) fn radii(&self, unit_size: u32) -> [f32; 4] {
+    self.radius.map(|r| r * unit_size as f32)
+  }
+
+  pub fn rrect(&self, rect: Rect, unit_size: u32) -> RRect {
+    rounded_rect(rect, self.radii(unit_size), [0.0; 4])
+  }
+
+  /* Display item of the background in rect, None if nothing is visible */
+  pub fn fill_item(&self, rect: Rect, unit_size: u32) -> Option<DisplayItem> {
+    let radii = self.radii(unit_size);
+    let rounded = radii.iter().any(|r| *r > 0.0);
+
+    match self.background {
+      Background::None => None,
+      Background::Color(color) => {
+        let quad = quad(rect, color.into());
+        Some(if rounded { DisplayItem::RoundedQuad { quad, radii } } else { DisplayItem::Quad(quad) })
+      }
+      Background::Gradient { from, to, axis } => {
+        Some(DisplayItem::Gradient { bounds: rect.into(), radii, from: from.into(), to: to.into(), axis })
+      }
+    }
+  }
+
+  /* Display item of the border along the inside of rect, border is [left, right, top, bottom] in units */
+  pub fn border_item(&self, rect: Rect, border: [f32; 4], unit_size: u32) -> Option<DisplayItem> {
+    let color = self.border_color?;
+    if border.iter().all(|b| *b <= 0.0) { return None; }
+
+    Some(DisplayItem::Border {
+      bounds: rect.into(),
+      radii: self.radii(unit_size),
+      widths: border.map(|b| b * unit_size as f32),
+      color: color.into(),
+    })
+  }
+}
+
+
+fn quad(rect: Rect, color: objects::Color) -> Quad2D {
+  Quad2D::new(rect.left, rect.top, rect.width(), rect.height(), color)
+}
+
+fn mix(a: Color, b: Color, t: f32) -> Color {
+  let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+  Color::from_argb(channel(a.a(), b.a()), channel(a.r(), b.r()), channel(a.g(), b.g()), channel(a.b(), b.b()))
+}
+
+
+/* Radii are shrunk by the adjacent border widths ([left, right, top, bottom]) so the inner edge stays parallel */
+pub(crate) fn rounded_rect(rect: Rect, radii: [f32; 4], inset: [f32; 4]) -> RRect {
+  let [left, right, top, bottom] = inset;
+  let [top_left, top_right, bottom_right, bottom_left] = radii;
+
+  RRect::new_rect_radii(rect, &[
+    Vector::new((top_left - left).max(0.0), (top_left - top).max(0.0)),
+    Vector::new((top_right - right).max(0.0), (top_right - top).max(0.0)),
+    Vector::new((bottom_right - right).max(0.0), (bottom_right - bottom).max(0.0)),
+    Vector::new((bottom_left - left).max(0.0), (bottom_left - bottom).max(0.0)),
+  ])
+}
+
+
+/* Whether the point lies within the rounded rect, points beyond the ellipse of a corner are outside */
+pub(crate) fn rrect_contains(rrect: &RRect, x: f32, y: f32) -> bool {
+  let rect = rrect.rect();
+  if x < rect.left || x >= rect.right || y < rect.top || y >= rect.bottom { return false }
+
+  // Distances past the centre of the ellipse of the corner the point is in, radii never reach past the middle
+  let [upper_left, upper_right, lower_right, lower_left] = *rrect.radii_ref();
+  let (radii, outside_x, outside_y) = match (x < rect.center_x(), y < rect.center_y()) {
+    (true, true) => (upper_left, rect.left + upper_left.x - x, rect.top + upper_left.y - y),
+    (false, true) => (upper_right, x - (rect.right - upper_right.x), rect.top + upper_right.y - y),
+    (false, false) => (lower_right, x - (rect.right - lower_right.x), y - (rect.bottom - lower_right.y)),
+    (true, false) => (lower_left, rect.left + lower_left.x - x, y - (rect.bottom - lower_left.y)),
+  };
+  if outside_x <= 0.0 || outside_y <= 0.0 { return true }
+
+  (outside_x / radii.x).powi(2) + (outside_y / radii.y).powi(2) <= 1.0
+}
+
+
+/* Interaction states in increasing priority, variants of later states override earlier ones */
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum State {
+  Hovered,
+  Focused,
+  Pressed,
+  Disabled,
+}
+
+
+#[derive(PartialEq, Clone, Copy, Default, Debug)]
+pub struct Interaction {
+  pub hovered: bool,
+  pub focused: bool,
+  pub pressed: bool,
+  pub disabled: bool,
+}
+
+
+impl Interaction {
+
+  pub fn is(&self, state: State) -> bool {
+    match state {
+      State::Hovered => self.hovered,
+      State::Focused => self.focused,
+      State::Pressed => self.pressed,
+      State::Disabled => self.disabled,
+    }
+  }
+}
+
+
+struct Animation {
+  from: Style,
+  to: Style,
+  start: Instant,
+}
+
+
+/* State dependent style variants of an element and the style it was drawn with last */
+#[derive(Default)]
+pub struct Variants {
+  pub interaction: Interaction,
+  pub transition: Option<Duration>,
+
+  pub(crate) own: Vec<(State, Properties)>,
+  pub(crate) themed: Vec<(State, Properties)>,
+
+  animation: Option<Animation>,
+  current: Option<Style>,
+}
+
+
+impl Variants {
+
+  /* Style the element should have in its current interaction state */
+  pub fn target(&self, base: &Style) -> Style {
+    let mut style = *base;
+    for state in [State::Hovered, State::Focused, State::Pressed, State::Disabled] {
+      if !self.interaction.is(state) { continue; }
+
+      // Variants set on the element win over the theme's
+      for (_, properties) in self.themed.iter().chain(self.own.iter()).filter(|(s, _)| *s == state) {
+        properties.apply_style(&mut style);
+      }
+    }
+    style
+  }
+
+  /* Resolves the style to draw this frame, advancing a running transition */
+  pub fn resolve(&mut self, base: &Style, now: Instant) -> Style {
+    let target = self.target(base);
+
+    let Some(duration) = self.transition else {
+      self.current = Some(target);
+      return target;
+    };
+
+    let retarget = self.animation.as_ref().map_or(true, |a| a.to != target);
+    if retarget {
+      let from = self.current.unwrap_or(target);
+      self.animation = (from != target).then(|| Animation { from, to: target, start: now });
+    }
+
+    let style = match &self.animation {
+      Some(animation) => {
+        let t = now.duration_since(animation.start).as_secs_f32() / duration.as_secs_f32().max(f32::EPSILON);
+        if t >= 1.0 {
+          self.animation = None;
+          target
+        } else {
+          animation.from.lerp(&animation.to, ease(t))
+        }
+      }
+      None => target,
+    };
+
+    self.current = Some(style);
+    style
+  }
+
+  pub fn animating(&self) -> bool {
+    self.animation.is_some()
+  }
+
+  /* Style used in the last resolved frame */
+  pub fn current(&self) -> Option<&Style> {
+    self.current.as_ref()
+  }
+}
+
+
+/* Smoothstep easing for transitions */
+fn ease(t: f32) -> f32 {
+  t * t * (3.0 - 2.0 * t)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_state_priority() {
+    let mut variants = Variants::default();
+    variants.own.push((State::Pressed, Properties::new().background(Color::RED)));
+    variants.own.push((State::Hovered, Properties::new().background(Color::GREEN).opacity(0.5)));
+
+    let base = Style::new().background(Color::BLUE);
+    assert_eq!(variants.target(&base).background, Background::Color(Color::BLUE));
+
+    variants.interaction.hovered = true;
+    variants.interaction.pressed = true;
+    let style = variants.target(&base);
+    assert_eq!(style.background, Background::Color(Color::RED));
+    assert_eq!(style.opacity, 0.5);
+  }
+
+  #[test]
+  fn test_transition() {
+    let mut variants = Variants::default();
+    variants.transition = Some(Duration::from_millis(100));
+    variants.own.push((State::Hovered, Properties::new().opacity(0.0)));
+
+    let base = Style::new();
+    let start = Instant::now();
+    assert_eq!(variants.resolve(&base, start).opacity, 1.0);
+
+    variants.interaction.hovered = true;
+    assert_eq!(variants.resolve(&base, start).opacity, 1.0);
+    assert_eq!(variants.resolve(&base, start + Duration::from_millis(50)).opacity, 0.5);
+    assert!(variants.animating());
+
+    assert_eq!(variants.resolve(&base, start + Duration::from_millis(100)).opacity, 0.0);
+    assert!(!variants.animating());
+  }
+
+  #[test]
+  fn test_rrect_contains() {
+    let rrect = rounded_rect(Rect::from_wh(100.0, 50.0), [20.0, 0.0, 10.0, 0.0], [0.0; 4]);
+    assert!(rrect_contains(&rrect, 50.0, 25.0));
+    assert!(!rrect_contains(&rrect, 2.0, 2.0));
+    assert!(rrect_contains(&rrect, 8.0, 8.0));
+    assert!(rrect_contains(&rrect, 99.0, 1.0));
+    assert!(!rrect_contains(&rrect, 99.5, 49.5));
+    assert!(!rrect_contains(&rrect, 100.0, 25.0));
+  }
+}
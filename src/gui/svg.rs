@@ -0,0 +1,267 @@
+use std::cell::RefCell;
+
+use skia_safe::{svg::Dom, Canvas, Color, FontMgr, Picture, PictureRecorder, Rect};
+
+use super::{image::{Fit, ImageCache, ImageError, ImageSource}, layout::{Layout, LayoutElement, Size}, text::TextContext};
+use crate::objects::{DisplayItem, DisplayList};
+
+
+/* Parsed SVG document, sized once to the size its viewBox maps onto */
+#[derive(Clone)]
+pub struct SvgImage {
+  dom : Dom,
+  size : (f32, f32),
+}
+
+
+impl SvgImage {
+
+  pub fn parse(data : &[u8]) -> Option<Self> {
+    let mut dom = Dom::from_bytes(data, FontMgr::new()).ok()?;
+
+    // The viewBox gives the aspect ratio, width and height attributes only the default size
+    let root = dom.root();
+    let intrinsic = root.intrinsic_size();
+    let size = match root.view_box() {
+      Some(view_box) if !intrinsic.is_empty() => (intrinsic.width, intrinsic.width * view_box.height() / view_box.width()),
+      Some(view_box) => (view_box.width(), view_box.height()),
+      None => (intrinsic.width, intrinsic.height),
+    };
+
+    if size.0 <= 0.0 || size.1 <= 0.0 { return None }
+    dom.set_container_size(size);
+    Some(SvgImage { dom, size })
+  }
+
+  /* Default size in pixels, its aspect ratio is the one of the viewBox */
+  pub fn size(&self) -> (f32, f32) {
+    self.size
+  }
+
+  pub fn aspect_ratio(&self) -> f32 {
+    self.size.0 / self.size.1
+  }
+
+  /* Renders the document scaled into the rect */
+  pub fn render(&self, canvas : &Canvas, dst : Rect) {
+    canvas.save();
+    canvas.translate((dst.left, dst.top));
+    canvas.scale((dst.width() / self.size.0, dst.height() / self.size.1));
+    self.dom.render(canvas);
+    canvas.restore();
+  }
+
+  /* Records the document scaled to the size, placed at the origin */
+  pub fn picture(&self, size : (f32, f32)) -> Option<Picture> {
+    let bounds = Rect::from_wh(size.0, size.1);
+    let mut recorder = PictureRecorder::new();
+    self.render(recorder.begin_recording(bounds, None), bounds);
+    recorder.finish_recording_as_picture(None)
+  }
+}
+
+
+/* Content size of an SVG: an axis sized by content follows the other axis through the aspect ratio */
+fn content_size(size : (f32, f32), hsize : Size, vsize : Size, available : (u32, u32)) -> (u32, u32) {
+  let aspect = size.0 / size.1;
+  let (width, height) = match (hsize == Size::Content, vsize == Size::Content) {
+    (true, true) => size,
+    (true, false) => (available.1 as f32 * aspect, available.1 as f32),
+    (false, true) => (available.0 as f32, available.0 as f32 / aspect),
+    (false, false) => (available.0 as f32, available.1 as f32),
+  };
+  (width.round() as u32, height.round() as u32)
+}
+
+
+/* Vector icon, scaled crisply to its content box and optionally tinted with a single colour */
+pub struct SvgElement {
+  source : ImageSource,
+  layout : Layout,
+
+  tint : Option<Color>,
+
+  image : Option<SvgImage>,
+  stale : bool, // source changed since images were last loaded
+
+  rendered : RefCell<Option<((f32, f32), Picture)>>, // document recorded at the size it was last drawn at
+}
+
+
+impl LayoutElement for SvgElement {
+  fn layout(&self) -> &Layout {
+    &self.layout
+  }
+
+  fn layout_mut(&mut self) -> &mut Layout {
+    &mut self.layout
+  }
+
+  fn load_images(&mut self, images: &mut ImageCache) -> Result<(), ImageError> {
+    self.image = None;
+    self.stale = false;
+    self.rendered.replace(None);
+    self.image = Some(images.load_svg(&self.source)?);
+    Ok(())
+  }
+
+  fn images_stale(&self) -> bool {
+    self.stale
+  }
+
+  fn measure(&mut self, width: u32, height: u32, _unit_size: u32, _text: &TextContext) -> Option<(u32, u32)> {
+    let image = self.image.as_ref()?;
+    Some(content_size(image.size(), self.layout.horizontal(), self.layout.vertical(), (width, height)))
+  }
+
+  fn display(&self, list: &mut DisplayList, unit_size: u32) {
+    list.extend(self.layout.current_style().fill_item(self.layout.inner_rect(), unit_size));
+
+    let Some(image) = &self.image else { return };
+
+    let computed = &self.layout.computed;
+    let bounds = Rect::from_xywh(
+      computed.content_pos.0 as f32,
+      computed.content_pos.1 as f32,
+      computed.content_dim.0 as f32,
+      computed.content_dim.1 as f32,
+    );
+
+    let (_, dst) = Fit::Contain.rects(image.size(), bounds);
+    let Some(picture) = self.rendered((dst.width(), dst.height())) else { return };
+
+    list.push(DisplayItem::Svg { picture, dst: dst.into(), tint: self.tint.map(Into::into) });
+  }
+}
+
+impl SvgElement {
+
+  pub fn new<S: Into<ImageSource>>(source : S) -> Self {
+    SvgElement {
+      source: source.into(),
+      layout: Default::default(),
+
+      tint: None,
+
+      image: None,
+      stale: false,
+
+      rendered: RefCell::new(None),
+    }
+  }
+
+  pub fn source(&self) -> &ImageSource {
+    &self.source
+  }
+
+  /* Shows another document, the application loads it through its cache before the next frame is laid out */
+  pub fn set_source<S: Into<ImageSource>>(&mut self, source : S) {
+    self.source = source.into();
+    self.image = None;
+    self.stale = true;
+    self.rendered.replace(None);
+    self.layout.damage();
+  }
+
+  /* Recolours the icon, meant for monochrome icons */
+  pub fn tint(mut self, color : Color) -> Self {
+    self.tint = Some(color);
+    self
+  }
+
+  pub fn set_tint(&mut self, color : Option<Color>) {
+    self.tint = color;
+    self.layout.damage();
+  }
+
+  /* Parsed document, None until loaded */
+  pub fn image(&self) -> Option<&SvgImage> {
+    self.image.as_ref()
+  }
+
+  /* Document recorded at the size, rendered again only when the size changes */
+  fn rendered(&self, size : (f32, f32)) -> Option<Picture> {
+    let image = self.image.as_ref()?;
+    if size.0 <= 0.0 || size.1 <= 0.0 { return None }
+
+    if let Some((rendered, picture)) = &*self.rendered.borrow() {
+      if *rendered == size { return Some(picture.clone()) }
+    }
+
+    let picture = image.picture(size)?;
+    self.rendered.replace(Some((size, picture.clone())));
+    Some(picture)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use skia_safe::surfaces;
+
+  use super::*;
+  use crate::testing::lay_out;
+
+  const ICON: &[u8] = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 32 16"><rect width="32" height="16"/></svg>"#;
+
+  #[test]
+  fn test_view_box_size() {
+    let image = SvgImage::parse(ICON).unwrap();
+    assert_eq!(image.size(), (32.0, 16.0));
+    assert_eq!(image.aspect_ratio(), 2.0);
+
+    assert!(SvgImage::parse(b"<html></html>").is_none());
+  }
+
+  #[test]
+  fn test_content_size() {
+    let size = (32.0, 16.0);
+    assert_eq!(content_size(size, Size::Content, Size::Content, (100, 100)), (32, 16));
+    assert_eq!(content_size(size, Size::Content, Size::Unit(2.0), (100, 20)), (40, 20));
+    assert_eq!(content_size(size, Size::Max, Size::Content, (100, 300)), (100, 50));
+  }
+
+  #[test]
+  fn test_set_source() {
+    let mut cache = ImageCache::new();
+    let mut element = SvgElement::new(ICON);
+    element.load_images(&mut cache).unwrap();
+    assert!(!element.images_stale());
+
+    element.set_source("missing.svg");
+    assert!(element.images_stale() && element.image().is_none());
+
+    assert!(element.load_images(&mut cache).is_err());
+    assert!(!element.images_stale());
+
+    element.set_source(ICON);
+    element.load_images(&mut cache).unwrap();
+    assert!(element.image().is_some());
+  }
+
+  #[test]
+  fn test_rendered_per_size() {
+    let mut element = SvgElement::new(ICON);
+    element.load_images(&mut ImageCache::new()).unwrap();
+
+    let first = element.rendered((32.0, 16.0)).unwrap();
+    assert_eq!(element.rendered((32.0, 16.0)).unwrap().unique_id(), first.unique_id());
+    assert_ne!(element.rendered((64.0, 32.0)).unwrap().unique_id(), first.unique_id());
+    assert!(element.rendered((0.0, 16.0)).is_none());
+  }
+
+  #[test]
+  fn test_tinted_item() {
+    let mut element = SvgElement::new(ICON).tint(Color::RED);
+    element.load_images(&mut ImageCache::new()).unwrap();
+    lay_out(&mut element);
+
+    let list = (&element as &dyn LayoutElement).display_list(10);
+    let Some(DisplayItem::Svg { dst, .. }) = list.items().last() else { panic!("no svg item") };
+    assert_eq!((dst.width, dst.height), (32.0, 16.0));
+
+    let mut surface = surfaces::raster_n32_premul((40, 20)).unwrap();
+    list.replay(surface.canvas());
+    assert_eq!(surface.peek_pixels().unwrap().get_color((16, 8)), Color::RED);
+  }
+}
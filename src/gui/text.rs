@@ -1,17 +1,168 @@
-use skia_safe::{Font, Paint};
+use std::rc::Rc;
 
-use super::layout::{self, Layout, LayoutElement, Size};
+use skia_safe::{font_style::{Slant, Weight, Width}, textlayout::{FontCollection, Paragraph, ParagraphBuilder, ParagraphStyle, TextDirection, TextStyle}, Color, Font, FontMgr, FontStyle};
 
+use super::layout::{Alignment, Layout, LayoutElement};
+use crate::objects::{DisplayItem, DisplayList};
 
 
+#[derive(PartialEq, Clone, Debug)]
+pub struct FontSpec {
+  pub family: Option<String>, // None uses the default system family
+  pub size: f32,              // in pixels
+  pub weight: Weight,
+  pub slant: Slant,
+  pub fallback: Vec<String>,  // families tried in order for characters the main family has no glyph for
+}
+
+
+/* Colour emoji families of the common platforms, tried after the fallback families of a spec */
+pub const EMOJI_FAMILIES: [&str; 3] = ["Noto Color Emoji", "Apple Color Emoji", "Segoe UI Emoji"];
+
+
+impl Default for FontSpec {
+    fn default() -> Self {
+        Self { family: None, size: 14.0, weight: Weight::NORMAL, slant: Slant::Upright, fallback: Vec::new() }
+    }
+}
+
+
+impl FontSpec {
+
+  pub fn style(&self) -> FontStyle {
+    FontStyle::new(self.weight, Width::NORMAL, self.slant)
+  }
+
+  /* Paragraph text style with this font in the given colour, its size multiplied with the text scale */
+  pub fn text_style(&self, color: Color, scale: f32) -> TextStyle {
+    let mut style = TextStyle::new();
+    style.set_font_size(self.size * scale);
+    style.set_font_style(self.style());
+    style.set_color(color);
+    // Without any family the default one is used and the system fallback finds the emoji
+    if self.family.is_some() || !self.fallback.is_empty() {
+      let families: Vec<&str> = self.family.iter().chain(&self.fallback).map(String::as_str).chain(EMOJI_FAMILIES).collect();
+      style.set_font_families(&families);
+    }
+    style
+  }
+
+  /* Resolves the font through the collection, falling back to the default family */
+  pub fn font(&self, collection: &FontCollection) -> Font {
+    let families: Vec<&String> = self.family.iter().collect();
+    let typeface = collection.clone().find_typefaces(&families, self.style()).into_iter().next();
+
+    match typeface {
+      Some(typeface) => Font::from_typeface(typeface, self.size),
+      None => Font::default().with_size(self.size).unwrap_or_default(),
+    }
+  }
+
+  /* Recommended distance between two baselines in pixels, unaffected by the text scale */
+  pub fn line_height(&self, collection: &FontCollection) -> f32 {
+    let (spacing, _) = self.font(collection).metrics();
+    spacing
+  }
+}
+
+
+/* Fonts text is shaped with, handed down to the elements when they are measured */
+#[derive(Clone, Debug)]
+pub struct TextContext {
+  pub collection: FontCollection, // resolves the families of the font specs
+  pub scale: f32,                 // factor all font sizes are multiplied with (the zoom)
+  pub hinting: bool,              // fits glyph outlines to the pixel grid, without it text renders the same on every FreeType build
+}
+
+
+impl TextContext {
+
+  pub fn new(collection: FontCollection) -> Self {
+    TextContext { collection, scale: 1.0, hinting: true }
+  }
+
+  /* Style paragraphs start from, glyphs are always antialiased and positioned to a quarter pixel */
+  pub fn paragraph_style(&self) -> ParagraphStyle {
+    let mut style = ParagraphStyle::new();
+    if !self.hinting {
+      style.turn_hinting_off();
+    }
+    style
+  }
+}
+
+/* The system fonts at a scale of 1 */
+impl Default for TextContext {
+  fn default() -> Self {
+    let mut collection = FontCollection::new();
+    collection.set_default_font_manager(FontMgr::new(), None);
+    collection.enable_font_fallback();
+    TextContext::new(collection)
+  }
+}
+
+
+/* Paragraph direction given by the first strong character of the text (as in the Unicode bidi algorithm) */
+pub fn base_direction(text: &str) -> TextDirection {
+  for c in text.chars() {
+    match c as u32 {
+      // Arabic-Indic digits are weak like the European ones
+      0x0660..=0x0669 | 0x06F0..=0x06F9 => {}
+      0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF | 0x10800..=0x10FFF | 0x1E800..=0x1EFFF => return TextDirection::RTL,
+      _ if c.is_alphabetic() => return TextDirection::LTR,
+      _ => {}
+    }
+  }
+  TextDirection::LTR
+}
+
+
+/*
+Shapes a single line of text, laid out to its natural width.
+Shaping goes through HarfBuzz, runs are reordered by the bidi algorithm and characters missing from the
+families of the spec are resolved through the fallback of the collection.
+*/
+pub fn shape_line(text: &str, spec: &FontSpec, color: Color, context: &TextContext) -> Paragraph {
+  let text_style = spec.text_style(color, context.scale);
+
+  let mut style = context.paragraph_style();
+  style.set_text_style(&text_style);
+  style.set_text_direction(base_direction(text));
+  style.set_max_lines(1);
+
+  let mut builder = ParagraphBuilder::new(&style, context.collection.clone());
+  builder.push_style(&text_style);
+  builder.add_text(text);
+
+  let mut paragraph = builder.build();
+  paragraph.layout(f32::INFINITY);
+  // Lay out again at the natural width, so right to left lines start at the origin
+  paragraph.layout(paragraph.max_intrinsic_width().ceil());
+  paragraph
+}
+
+
+/* Offset of content with the given size inside the available space */
+pub fn align(available: f32, size: f32, alignment: Alignment) -> f32 {
+  match alignment {
+    Alignment::Start => 0.0,
+    Alignment::End => available - size,
+    Alignment::Center | Alignment::Even => (available - size) / 2.0,
+  }
+}
 
 
 pub struct TextElement {
   content : String,
   layout: Layout,
 
-  paint : Paint,
-  font : Font,
+  spec : FontSpec,
+
+  halign : Alignment,
+  valign : Alignment,
+
+  paragraph : Option<Rc<Paragraph>>, // shared with the display items painting it
+  built_with : Option<(Color, TextContext)>, // colour and fonts the line was shaped with
 }
 
 
@@ -23,24 +174,260 @@ impl LayoutElement for TextElement {
   fn layout_mut(&mut self) -> &mut Layout {
     &mut self.layout
   }
+
+  fn font_families(&self) -> Vec<&str> {
+    self.spec.family.as_deref().into_iter().collect()
+  }
+
+  fn update_unit_size(&mut self, _unit_size: u32) {
+    self.invalidate();
+  }
+
+  fn measure(&mut self, _width: u32, _height: u32, _unit_size: u32, text: &TextContext) -> Option<(u32, u32)> {
+    let color = self.layout.variants.target(&self.layout.style).color;
+    if self.built_with.as_ref().map(|(built, _)| *built) != Some(color) {
+      self.paragraph = Some(Rc::new(shape_line(&self.content, &self.spec, color, text)));
+      self.built_with = Some((color, text.clone()));
+    }
+
+    let paragraph = self.paragraph.as_ref()?;
+    Some((paragraph.max_intrinsic_width().ceil() as u32, paragraph.height().ceil() as u32))
+  }
+
+  fn display(&self, list: &mut DisplayList, unit_size: u32) {
+    let style = self.layout.current_style();
+    list.extend(style.fill_item(self.layout.inner_rect(), unit_size));
+
+    let Some(paragraph) = &self.paragraph else { return };
+    let computed = &self.layout.computed;
+
+    let x = computed.content_pos.0 as f32 + align(computed.content_dim.0 as f32, paragraph.max_width(), self.halign);
+    let y = computed.content_pos.1 as f32 + align(computed.content_dim.1 as f32, paragraph.height(), self.valign);
+
+    // Shaped for the colour of the target style, a colour transition in progress needs its own line
+    let Some((color, text)) = &self.built_with else { return };
+    let paragraph = match *color == style.color {
+      true => paragraph.clone(),
+      false => Rc::new(shape_line(&self.content, &self.spec, style.color, text)),
+    };
+    list.push(DisplayItem::Text {
+      text: self.content.clone(),
+      origin: (x, y),
+      font: self.spec.clone(),
+      size: self.spec.size * text.scale,
+      color: style.color.into(),
+      paragraph,
+    });
+  }
 }
 
 impl TextElement {
 
   pub fn new<T: Into<String>>(content : T) -> Self {
+    TextElement {
+      content: content.into(),
+      layout: Default::default(),
+      spec: FontSpec::default(),
+      halign: Alignment::Start,
+      valign: Alignment::Center,
+      paragraph: None,
+      built_with: None,
+    }
+    .class("text")
+  }
+
+  pub fn content(&self) -> &str {
+    &self.content
+  }
+
+  pub fn set_content<T: Into<String>>(&mut self, content : T) {
+    self.content = content.into();
+    self.invalidate();
+  }
 
-    TextElement { content: content.into(), layout: Default::default(), paint: Paint::default(), font: Font::default() }
+  pub fn font_family<T: Into<String>>(mut self, family : T) -> Self {
+    self.spec.family = Some(family.into());
+    self.invalidate();
+    self
+  }
 
+  /* Families tried in order for characters the main family has no glyph for (e.g. a CJK or emoji family) */
+  pub fn font_fallback<T: Into<String>>(mut self, families : impl IntoIterator<Item = T>) -> Self {
+    self.spec.fallback = families.into_iter().map(Into::into).collect();
+    self.invalidate();
+    self
   }
 
-  fn update_unit_size(&mut self, unit_size : u32) {
-    let (length , rect) = self.font.measure_str(&self.content, Some(&self.paint));
+  pub fn font_size(mut self, size : f32) -> Self {
+    self.spec.size = size;
+    self.invalidate();
+    self
+  }
+
+  pub fn weight(mut self, weight : Weight) -> Self {
+    self.spec.weight = weight;
+    self.invalidate();
+    self
+  }
 
-    self.layout.hsize = Size::Unit(rect.width() / unit_size as f32);
-    self.layout.vsize = Size::Unit(rect.height() / unit_size as f32);  
+  pub fn bold(self) -> Self {
+    self.weight(Weight::BOLD)
+  }
+
+  pub fn italic(mut self) -> Self {
+    self.spec.slant = Slant::Italic;
+    self.invalidate();
+    self
+  }
+
+  /* Alignment of the text inside the content box of the element */
+  pub fn text_align(mut self, halign : Alignment, valign : Alignment) -> Self {
+    self.halign = halign;
+    self.valign = valign;
+    self.layout.damage();
+    self
+  }
+
+  fn invalidate(&mut self) {
+    self.paragraph = None;
+    self.built_with = None;
+    self.layout.damage();
   }
 }
 
 
+#[cfg(test)]
+mod tests {
+  use std::time::Instant;
+
+  use skia_safe::textlayout::{RectHeightStyle, RectWidthStyle, TypefaceFontProvider};
+
+  use super::*;
+  use crate::testing::lay_out;
+
+  const LATIN: &[u8] = include_bytes!("../../assets/fonts/Cantarell-Regular.ttf");
+  const DEJAVU: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+  const EMOJI: &[u8] = include_bytes!("../../assets/fonts/NotoColorEmoji-Partial.ttf"); // keycap characters only
+
+  /* Only the bundled fonts and no system fallback, so results don't depend on the machine */
+  fn bundled() -> TextContext {
+    fonts(&[LATIN, DEJAVU])
+  }
+
+  fn fonts(fonts: &[&[u8]]) -> TextContext {
+    let mut provider = TypefaceFontProvider::new();
+    for data in fonts {
+      let typeface = FontMgr::new().new_from_data(data, None).unwrap();
+      provider.register_typeface(typeface, None);
+    }
+
+    let manager: FontMgr = provider.into();
+    let mut collection = FontCollection::new();
+    collection.set_asset_font_manager(manager);
+    collection.disable_font_fallback();
+    TextContext::new(collection)
+  }
+
+  fn spec(family: &str, fallback: &[&str]) -> FontSpec {
+    FontSpec {
+      family: Some(family.into()),
+      fallback: fallback.iter().map(|f| f.to_string()).collect(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_base_direction() {
+    assert_eq!(base_direction("Hello"), TextDirection::LTR);
+    assert_eq!(base_direction("שלום"), TextDirection::RTL);
+    assert_eq!(base_direction("123 مرحبا abc"), TextDirection::RTL);
+    assert_eq!(base_direction("(1) Hello שלום"), TextDirection::LTR);
+    assert_eq!(base_direction("١٢٣ abc"), TextDirection::LTR);
+    assert_eq!(base_direction("۱۲ مرحبا"), TextDirection::RTL);
+    assert_eq!(base_direction(""), TextDirection::LTR);
+  }
+
+  #[test]
+  fn test_fallback_chain() {
+    let fonts = bundled();
+    let text = "Hello שלום مرحبا";
+
+    let mut missing = shape_line(text, &spec("Cantarell", &[]), Color::BLACK, &fonts);
+    assert!(missing.unresolved_glyphs().unwrap_or(0) > 0);
+
+    let mut resolved = shape_line(text, &spec("Cantarell", &["DejaVu Sans"]), Color::BLACK, &fonts);
+    assert_eq!(resolved.unresolved_glyphs(), Some(0));
+  }
+
+  #[test]
+  fn test_emoji_fallback() {
+    // Only the colour emoji font is there, it is found without being listed in the spec
+    let fonts = fonts(&[EMOJI]);
+
+    let mut emoji = shape_line("#1*", &spec("Cantarell", &[]), Color::BLACK, &fonts);
+    assert_eq!(emoji.unresolved_glyphs(), Some(0));
+    assert!(emoji.get_fonts().iter().all(|info| info.font.typeface().family_name() == EMOJI_FAMILIES[0]));
+
+    let mut latin = shape_line("a", &spec("Cantarell", &[]), Color::BLACK, &fonts);
+    assert!(latin.unresolved_glyphs().unwrap_or(0) > 0);
+  }
+
+  #[test]
+  fn test_bidi_reordering() {
+    let fonts = bundled();
+    let text = "abc שלום"; // UTF-16 indices 4..8 are the Hebrew word
+    let paragraph = shape_line(text, &spec("DejaVu Sans", &[]), Color::BLACK, &fonts);
 
+    let left = |range: std::ops::Range<usize>| paragraph.get_rects_for_range(range, RectHeightStyle::Tight, RectWidthStyle::Tight)[0].rect.left;
 
+    // The Latin run comes first, the Hebrew word after it reads from right to left
+    assert!(left(0..1) < left(4..5));
+    assert!(left(7..8) < left(4..5));
+
+    let rtl = shape_line("שלום", &spec("DejaVu Sans", &[]), Color::BLACK, &fonts);
+    assert!(rtl.get_rects_for_range(0..1, RectHeightStyle::Tight, RectWidthStyle::Tight)[0].rect.left >= 0.0);
+  }
+
+  #[test]
+  fn test_text_scale() {
+    let measure = |fonts: &TextContext| TextElement::new("Hello").font_family("DejaVu Sans").measure(1000, 1000, 10, fonts).unwrap();
+
+    let mut fonts = bundled();
+    let (width, height) = measure(&fonts);
+    fonts.scale = 2.0;
+    let (scaled_width, scaled_height) = measure(&fonts);
+    assert!(scaled_width.abs_diff(2 * width) <= 1 && scaled_height.abs_diff(2 * height) <= 1);
+  }
+
+  #[test]
+  fn test_display_reuses_line() {
+    let mut element = TextElement::new("Hello");
+    lay_out(&mut element);
+
+    // The line shaped for measuring is painted, not shaped again
+    let list = (&element as &dyn LayoutElement).display_list(10);
+    let Some(DisplayItem::Text { paragraph, .. }) = list.items().last() else { panic!("no text item") };
+    assert!(Rc::ptr_eq(paragraph, element.paragraph.as_ref().unwrap()));
+  }
+
+  #[test]
+  fn test_display_scaled() {
+    let mut element = TextElement::new("Hello");
+    let mut fonts = bundled();
+    let mut display = |fonts: &TextContext| {
+      let root: &mut dyn LayoutElement = &mut element;
+      root.update_unit_size(10);
+      root.calculate(100, 100, 10, fonts);
+      root.animate(Instant::now());
+      root.display_list(10)
+    };
+
+    let list = display(&fonts);
+    fonts.scale = 2.0;
+    let scaled = display(&fonts);
+
+    let Some(DisplayItem::Text { size, .. }) = scaled.items().last() else { panic!("no text item") };
+    assert_eq!(*size, 28.0);
+    assert_eq!(scaled.diff(&list), vec![scaled.len() - 1]);
+  }
+}
@@ -0,0 +1,420 @@
+use skia_safe::Color;
+
+use super::{layout::{Axis, Layout, LayoutElement, Size}, style::{Background, State, Style}};
+
+
+/*
+A theme is an ordered list of rules, every rule maps a selector onto a set of properties.
+Rules are applied by specificity (Any < Class < Id) and in declaration order within the same specificity,
+so later rules win. Properties set on the element directly, by its builders or by changing its layout at runtime,
+win over every rule like inline styles do.
+Rules with a state become style variants of the element and only take effect while it is in that state.
+*/
+
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Selector {
+  Any,
+  Class(String),
+  Id(String), // matches the name of the element (e.g. Container::name)
+}
+
+
+impl Selector {
+
+  pub fn class<T: Into<String>>(class: T) -> Self { Selector::Class(class.into()) }
+
+  pub fn id<T: Into<String>>(id: T) -> Self { Selector::Id(id.into()) }
+
+  fn specificity(&self) -> u32 {
+    match self {
+      Selector::Any => 0,
+      Selector::Class(_) => 1,
+      Selector::Id(_) => 2,
+    }
+  }
+
+  pub fn matches(&self, element: &dyn LayoutElement) -> bool {
+    match self {
+      Selector::Any => true,
+      Selector::Class(class) => element.layout().classes.iter().any(|c| c == class),
+      Selector::Id(id) => element.name() == Some(id.as_str()),
+    }
+  }
+}
+
+
+/* Partial set of style and layout properties, unset properties are left untouched */
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct Properties {
+  pub color: Option<Color>,
+  pub background: Option<Background>,
+  pub border_color: Option<Color>,
+  pub radius: Option<[f32; 4]>,
+  pub opacity: Option<f32>,
+
+  pub hsize: Option<Size>,
+  pub vsize: Option<Size>,
+  pub margin: Option<[f32; 4]>,
+  pub padding: Option<[f32; 4]>,
+  pub border: Option<[f32; 4]>,
+}
+
+
+impl Properties {
+
+  pub fn new() -> Self { Default::default() }
+
+  /* Snapshot of every property currently set on the layout */
+  pub fn of(layout: &Layout) -> Self {
+    Properties {
+      hsize: Some(layout.hsize),
+      vsize: Some(layout.vsize),
+      margin: Some(layout.margin),
+      padding: Some(layout.padding),
+      border: Some(layout.border),
+      ..Properties::of_style(&layout.style)
+    }
+  }
+
+  /* Snapshot of every style property */
+  pub fn of_style(style: &Style) -> Self {
+    Properties {
+      color: Some(style.color),
+      background: Some(style.background),
+      border_color: style.border_color,
+      radius: Some(style.radius),
+      opacity: Some(style.opacity),
+      ..Default::default()
+    }
+  }
+
+  /* Sets every property other sets, keeping the rest */
+  pub fn merge(&mut self, other: &Properties) {
+    self.color = other.color.or(self.color);
+    self.background = other.background.or(self.background);
+    self.border_color = other.border_color.or(self.border_color);
+    self.radius = other.radius.or(self.radius);
+    self.opacity = other.opacity.or(self.opacity);
+    self.hsize = other.hsize.or(self.hsize);
+    self.vsize = other.vsize.or(self.vsize);
+    self.margin = other.margin.or(self.margin);
+    self.padding = other.padding.or(self.padding);
+    self.border = other.border.or(self.border);
+  }
+
+  /* These properties without the ones other sets */
+  pub fn without(&self, other: &Properties) -> Properties {
+    fn unless<T: Copy>(value: Option<T>, other: Option<T>) -> Option<T> {
+      if other.is_some() { None } else { value }
+    }
+
+    Properties {
+      color: unless(self.color, other.color),
+      background: unless(self.background, other.background),
+      border_color: unless(self.border_color, other.border_color),
+      radius: unless(self.radius, other.radius),
+      opacity: unless(self.opacity, other.opacity),
+      hsize: unless(self.hsize, other.hsize),
+      vsize: unless(self.vsize, other.vsize),
+      margin: unless(self.margin, other.margin),
+      padding: unless(self.padding, other.padding),
+      border: unless(self.border, other.border),
+    }
+  }
+
+  /* Properties whose value differs from before */
+  pub fn changed(&self, before: &Properties) -> Properties {
+    fn changed<T: PartialEq + Copy>(now: Option<T>, before: Option<T>) -> Option<T> {
+      if now != before { now } else { None }
+    }
+
+    Properties {
+      color: changed(self.color, before.color),
+      background: changed(self.background, before.background),
+      border_color: changed(self.border_color, before.border_color),
+      radius: changed(self.radius, before.radius),
+      opacity: changed(self.opacity, before.opacity),
+      hsize: changed(self.hsize, before.hsize),
+      vsize: changed(self.vsize, before.vsize),
+      margin: changed(self.margin, before.margin),
+      padding: changed(self.padding, before.padding),
+      border: changed(self.border, before.border),
+    }
+  }
+
+  pub fn color(mut self, color: Color) -> Self {
+    self.color = Some(color);
+    self
+  }
+
+  pub fn background(mut self, color: Color) -> Self {
+    self.background = Some(Background::Color(color));
+    self
+  }
+
+  pub fn gradient(mut self, from: Color, to: Color, axis: Axis) -> Self {
+    self.background = Some(Background::Gradient { from, to, axis });
+    self
+  }
+
+  pub fn border(mut self, width: f32, color: Color) -> Self {
+    self.border = Some([width; 4]);
+    self.border_color = Some(color);
+    self
+  }
+
+  pub fn border_color(mut self, color: Color) -> Self {
+    self.border_color = Some(color);
+    self
+  }
+
+  pub fn rounded(mut self, radius: f32) -> Self {
+    self.radius = Some([radius; 4]);
+    self
+  }
+
+  pub fn rounded_all(mut self, top_left: f32, top_right: f32, bottom_right: f32, bottom_left: f32) -> Self {
+    self.radius = Some([top_left, top_right, bottom_right, bottom_left]);
+    self
+  }
+
+  pub fn opacity(mut self, opacity: f32) -> Self {
+    self.opacity = Some(opacity.clamp(0.0, 1.0));
+    self
+  }
+
+  pub fn width(mut self, sizing: Size) -> Self {
+    self.hsize = Some(sizing);
+    self
+  }
+
+  pub fn height(mut self, sizing: Size) -> Self {
+    self.vsize = Some(sizing);
+    self
+  }
+
+  pub fn margin(mut self, margin: f32) -> Self {
+    self.margin = Some([margin; 4]);
+    self
+  }
+
+  pub fn padding(mut self, padding: f32) -> Self {
+    self.padding = Some([padding; 4]);
+    self
+  }
+
+  pub fn apply_style(&self, style: &mut Style) {
+    if let Some(color) = self.color { style.color = color; }
+    if let Some(background) = self.background { style.background = background; }
+    if let Some(color) = self.border_color { style.border_color = Some(color); }
+    if let Some(radius) = self.radius { style.radius = radius; }
+    if let Some(opacity) = self.opacity { style.opacity = opacity; }
+  }
+
+  pub fn apply(&self, layout: &mut Layout) {
+    self.apply_style(&mut layout.style);
+    if let Some(hsize) = self.hsize { layout.hsize = hsize; }
+    if let Some(vsize) = self.vsize { layout.vsize = vsize; }
+    if let Some(margin) = self.margin { layout.margin = margin; }
+    if let Some(padding) = self.padding { layout.padding = padding; }
+    if let Some(border) = self.border { layout.border = border; }
+  }
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Rule {
+  pub selector: Selector,
+  pub state: Option<State>,
+  pub properties: Properties,
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Theme {
+  pub name: String,
+  pub clear_color: Color,
+
+  rules: Vec<Rule>,
+}
+
+
+impl Theme {
+
+  pub fn new<T: Into<String>>(name: T) -> Self {
+    Theme { name: name.into(), clear_color: Color::WHITE, rules: Vec::new() }
+  }
+
+  pub fn clear_color(mut self, color: Color) -> Self {
+    self.clear_color = color;
+    self
+  }
+
+  pub fn rule(mut self, selector: Selector, properties: Properties) -> Self {
+    self.rules.push(Rule { selector, state: None, properties });
+    self
+  }
+
+  pub fn state_rule(mut self, selector: Selector, state: State, properties: Properties) -> Self {
+    self.rules.push(Rule { selector, state: Some(state), properties });
+    self
+  }
+
+  pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+    self.rules.iter()
+  }
+
+  /* Matching rules of the element, ordered from weakest to strongest */
+  pub fn matching(&self, element: &dyn LayoutElement) -> Vec<&Rule> {
+    let mut rules: Vec<_> = self.rules.iter().filter(|r| r.selector.matches(element)).collect();
+    rules.sort_by_key(|r| r.selector.specificity());
+    rules
+  }
+
+  /*
+  Restyles the whole tree: every element is reset to its properties before the first theme,
+  the rules are applied and the properties set on the element directly go on top.
+  */
+  pub fn apply(&self, root: &mut dyn LayoutElement) {
+    for element in root.iter_mut() {
+      let rules: Vec<Rule> = self.matching(&*element).into_iter().cloned().collect();
+
+      let layout = element.layout_mut();
+      let current = Properties::of(layout);
+      match &layout.themed {
+        // What changed since the last theme was set at runtime, it stays like everything set on the element
+        Some(themed) => {
+          let edits = current.changed(themed);
+          layout.inline.merge(&edits);
+        }
+        None => layout.base = Some(Box::new(current)),
+      }
+
+      let base = layout.base.clone().unwrap();
+      layout.style = Default::default();
+      base.apply(layout);
+      layout.variants.themed.clear();
+
+      let inline = layout.inline.clone();
+      for rule in rules {
+        match rule.state {
+          Some(state) => layout.variants.themed.push((state, rule.properties.without(&inline))),
+          None => rule.properties.apply(layout),
+        }
+      }
+      inline.apply(layout);
+      layout.themed = Some(Box::new(Properties::of(layout)));
+    }
+  }
+
+  pub fn light() -> Self {
+    Theme::new("light")
+      .clear_color(Color::from_rgb(236, 239, 244))
+      .rule(Selector::class("surface"), Properties::new().background(Color::from_rgb(236, 239, 244)))
+      .rule(Selector::class("text"), Properties::new().color(Color::from_rgb(46, 52, 64)))
+      .rule(Selector::class("panel"), Properties::new().background(Color::from_rgb(216, 222, 233)).rounded(0.5))
+      .rule(Selector::class("card"), Properties::new().background(Color::WHITE).border(0.1, Color::from_rgb(200, 205, 215)).rounded(0.5))
+      .rule(Selector::class("accent"), Properties::new().background(Color::from_rgb(94, 129, 172)))
+      .rule(Selector::class("button"), Properties::new().background(Color::from_rgb(94, 129, 172)).border(0.15, Color::from_rgb(94, 129, 172)).rounded(0.3))
+      .state_rule(Selector::class("button"), State::Hovered, Properties::new().background(Color::from_rgb(129, 161, 193)))
+      .state_rule(Selector::class("button"), State::Pressed, Properties::new().background(Color::from_rgb(76, 86, 106)))
+      .state_rule(Selector::class("button"), State::Focused, Properties::new().border_color(Color::from_rgb(46, 52, 64)))
+      .state_rule(Selector::class("button"), State::Disabled, Properties::new().opacity(0.4))
+  }
+
+  pub fn dark() -> Self {
+    Theme::new("dark")
+      .clear_color(Color::from_rgb(46, 52, 64))
+      .rule(Selector::class("surface"), Properties::new().background(Color::from_rgb(46, 52, 64)))
+      .rule(Selector::class("text"), Properties::new().color(Color::from_rgb(236, 239, 244)))
+      .rule(Selector::class("panel"), Properties::new().background(Color::from_rgb(59, 66, 82)).rounded(0.5))
+      .rule(Selector::class("card"), Properties::new().background(Color::from_rgb(67, 76, 94)).border(0.1, Color::from_rgb(76, 86, 106)).rounded(0.5))
+      .rule(Selector::class("accent"), Properties::new().background(Color::from_rgb(136, 192, 208)))
+      .rule(Selector::class("button"), Properties::new().background(Color::from_rgb(94, 129, 172)).border(0.15, Color::from_rgb(94, 129, 172)).rounded(0.3))
+      .state_rule(Selector::class("button"), State::Hovered, Properties::new().background(Color::from_rgb(129, 161, 193)))
+      .state_rule(Selector::class("button"), State::Pressed, Properties::new().background(Color::from_rgb(59, 66, 82)))
+      .state_rule(Selector::class("button"), State::Focused, Properties::new().border_color(Color::from_rgb(236, 239, 244)))
+      .state_rule(Selector::class("button"), State::Disabled, Properties::new().opacity(0.4))
+  }
+
+  pub fn high_contrast() -> Self {
+    Theme::new("high-contrast")
+      .clear_color(Color::BLACK)
+      .rule(Selector::class("surface"), Properties::new().background(Color::BLACK))
+      .rule(Selector::class("text"), Properties::new().color(Color::WHITE))
+      .rule(Selector::class("panel"), Properties::new().background(Color::BLACK).border(0.2, Color::WHITE).rounded(0.0))
+      .rule(Selector::class("card"), Properties::new().background(Color::BLACK).border(0.2, Color::YELLOW).rounded(0.0))
+      .rule(Selector::class("accent"), Properties::new().background(Color::YELLOW))
+      .rule(Selector::class("button"), Properties::new().background(Color::BLACK).border(0.2, Color::WHITE).rounded(0.0))
+      .state_rule(Selector::class("button"), State::Hovered, Properties::new().border_color(Color::YELLOW))
+      .state_rule(Selector::class("button"), State::Pressed, Properties::new().background(Color::YELLOW))
+      .state_rule(Selector::class("button"), State::Focused, Properties::new().border_color(Color::CYAN))
+      .state_rule(Selector::class("button"), State::Disabled, Properties::new().border_color(Color::GRAY))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::gui::{container::Container, text::TextElement};
+
+  #[test]
+  fn test_specificity() {
+    let theme = Theme::new("test")
+      .rule(Selector::id("Header"), Properties::new().background(Color::RED))
+      .rule(Selector::class("panel"), Properties::new().background(Color::GREEN).margin(1.0))
+      .rule(Selector::Any, Properties::new().background(Color::BLUE));
+
+    let mut root = Container::new("Root")
+      .horizontal(vec![
+        Container::new("Header").class("panel").boxed(),
+        Container::new("Body").class("panel").boxed(),
+      ]);
+
+    theme.apply(&mut root as &mut dyn LayoutElement);
+
+    let layout = root.layout();
+    let children: Vec<_> = layout.layouts().collect();
+    assert_eq!(layout.style.background, Background::Color(Color::BLUE));
+    assert_eq!(children[0].style.background, Background::Color(Color::RED));
+    assert_eq!(children[1].style.background, Background::Color(Color::GREEN));
+    assert_eq!(children[1].margin, [1.0; 4]);
+  }
+
+  #[test]
+  fn test_switch_theme_restores_base() {
+    let mut root = Container::new("Root")
+      .class("card")
+      .width(Size::Unit(10.0));
+
+    Theme::high_contrast().apply(&mut root as &mut dyn LayoutElement);
+    assert_eq!(root.layout().style.border_color, Some(Color::YELLOW));
+
+    Theme::new("plain").apply(&mut root as &mut dyn LayoutElement);
+    assert_eq!(root.layout().style.border_color, None);
+    assert_eq!(root.layout().border, [0.0; 4]);
+    assert_eq!(root.layout().hsize, Size::Unit(10.0));
+  }
+
+  #[test]
+  fn test_inline_wins() {
+    let mut root = Container::new("Root")
+      .vertical(vec![
+        TextElement::new("Warning").color(Color::RED).boxed(),
+        TextElement::new("Body").boxed(),
+      ]);
+    let root = &mut root as &mut dyn LayoutElement;
+
+    Theme::dark().apply(root);
+    let colors = |root: &dyn LayoutElement| root.layout().layouts().map(|l| l.style.color).collect::<Vec<_>>();
+    assert_eq!(colors(root), [Color::RED, Color::from_rgb(236, 239, 244)]);
+
+    // Changed at runtime, kept through the next theme switch
+    root.layout_mut().style.background = Background::Color(Color::GREEN);
+    Theme::light().apply(root);
+    assert_eq!(root.layout().style.background, Background::Color(Color::GREEN));
+    assert_eq!(colors(root), [Color::RED, Color::from_rgb(46, 52, 64)]);
+  }
+}
@@ -0,0 +1,16 @@
+pub mod application;
+pub mod backend;
+pub mod damage;
+pub mod demo;
+pub mod export;
+pub mod objects;
+pub mod scheduler;
+pub mod screenshot;
+pub mod stats;
+pub mod user_event;
+
+#[cfg(test)]
+mod testing;
+
+#[macro_use]
+pub mod gui;
@@ -1,78 +1,13 @@
-#[allow(dead_code)]
-#[allow(unused_variables)]
-
-mod application;
-mod user_event;
-mod objects;
-
-#[macro_use]
-mod gui;
-
-use gui::{container::Container, layout::{Alignment, LayoutElement, Size}};
-use application::Application;
+use redwood::{application::Application, demo, gui::theme::Theme};
 
 
 fn main() -> Result<(), String>{
 
+    let fonts = demo::fonts().map_err(|e| e.to_string())?;
 
-
-    let layout = Container::new("Main Page")
-        .horizontal(vec![
-            Container::new("Left Part")
-                .width(Size::Unit(20.0))
-                .height(Size::Max)
-                .margin(0.5)
-                .padding(1.0)
-                .vertical(vec![
-                    Container::new("Left Part")
-                        .width(Size::Max)
-                        .height(Size::Unit(20.0))
-                        .boxed(),
-                    Container::new("Left Part")
-                        .width(Size::Max)
-                        .height(Size::Unit(20.0))
-                        .margin(0.7)
-                        .boxed(),
-                ])
-
-                .align(Alignment::Even)
-                .boxed(),
-            Container::new("Right Part")
-                .width(Size::Max)
-                .height(Size::Max)
-                .horizontal(vec![
-                    Container::new("Left Part")
-                    .width(Size::Same)
-                    .height(Size::Unit(20.0))
-                    .margin(0.7)
-                    .boxed(),
-                    Container::new("Left Part")
-                    .width(Size::Same)
-                    .height(Size::Unit(20.0))
-                    .margin(0.7)
-                    .boxed(),
-                    Container::new("Left Part")
-                    .width(Size::Same)
-                    .height(Size::Unit(40.0))
-                    .margin(0.7)
-                    .boxed(),
-                    Container::new("Left Part")
-                    .width(Size::Same)
-                    .height(Size::Unit(20.0))
-                    .margin(0.7)
-                    .boxed(),    
-                ])
-                .align(Alignment::Even)
-                .boxed(),
-            Container::new("Left Part")
-                .width(Size::Unit(20.0))
-                .height(Size::Max)
-                .boxed()
-        ]);
-
-    
-    let app = Application::new(layout);
-    app.run();
-
-    Ok(())
+    let app = Application::new(demo::layout())
+        .fonts(fonts)
+        .theme(Theme::light())
+        .themes([Theme::dark(), Theme::high_contrast()]);
+    app.run().map_err(|e| e.to_string())
 }
@@ -1,6 +1,11 @@
-use std::fmt::Display;
+use std::{fmt::Display, rc::Rc};
 
+use skia_safe::{canvas::SrcRectConstraint, color_filters, textlayout::Paragraph, BlendMode, Canvas, Image, Matrix, Paint, Picture, Rect, Shader, TileMode};
 
+use crate::gui::{image::Sampling, layout::Axis, style::rounded_rect, text::FontSpec};
+
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub struct Color {
   pub r: u8,
   pub g: u8,
@@ -9,16 +14,52 @@ pub struct Color {
 }
 
 
-/* 2D Quad starting in the upper left corner*/
+impl From<skia_safe::Color> for Color {
+  fn from(color: skia_safe::Color) -> Self {
+    Color { r: color.r(), g: color.g(), b: color.b(), a: color.a() }
+  }
+}
+
+impl From<Color> for skia_safe::Color {
+  fn from(color: Color) -> Self {
+    skia_safe::Color::from_argb(color.a, color.r, color.g, color.b)
+  }
+}
+
+
+/* Axis aligned rectangle in logical pixels */
+#[derive(PartialEq, Clone, Copy, Default, Debug)]
+pub struct Bounds {
+  pub x: f32,
+  pub y: f32,
+  pub width: f32,
+  pub height: f32,
+}
+
+
+impl From<Rect> for Bounds {
+  fn from(rect: Rect) -> Self {
+    Bounds { x: rect.left, y: rect.top, width: rect.width(), height: rect.height() }
+  }
+}
+
+impl From<Bounds> for Rect {
+  fn from(bounds: Bounds) -> Self {
+    Rect::from_xywh(bounds.x, bounds.y, bounds.width, bounds.height)
+  }
+}
 
+
+/* 2D Quad starting in the upper left corner*/
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub struct Quad2D {
-  pub x: u32, 
-  pub y: u32,
+  pub x: f32,
+  pub y: f32,
 
-  pub width: u32,
-  pub height: u32,
+  pub width: f32,
+  pub height: f32,
 
-  pub color : Color 
+  pub color : Color
 }
 
 impl Display for Quad2D {
@@ -33,7 +74,223 @@ impl Display for Quad2D {
 
 impl Quad2D {
 
-  pub fn new(x: u32, y: u32, width: u32, height: u32, color : Color) -> Self {
+  pub fn new(x: f32, y: f32, width: f32, height: f32, color : Color) -> Self {
     Self { x, y, width, height, color }
   }
-}
\ No newline at end of file
+
+  pub fn bounds(&self) -> Bounds {
+    Bounds { x: self.x, y: self.y, width: self.width, height: self.height }
+  }
+}
+
+
+/*
+One drawing operation of a frame, in logical pixels. Radii are in pixels, clockwise from the top left corner,
+border widths are [left, right, top, bottom]. Opacity is applied by a layer around the element and its children.
+*/
+#[derive(Clone, Debug)]
+pub enum DisplayItem {
+  Quad(Quad2D),
+  RoundedQuad { quad: Quad2D, radii: [f32; 4] },
+  Gradient { bounds: Bounds, radii: [f32; 4], from: Color, to: Color, axis: Axis },
+  Border { bounds: Bounds, radii: [f32; 4], widths: [f32; 4], color: Color }, // drawn along the inside of the bounds
+  Text { text: String, origin: (f32, f32), font: FontSpec, size: f32, color: Color, paragraph: Rc<Paragraph> }, // line or paragraph shaped from text, font and colour, size is the font size after the text scale, origin is its top left corner
+  Image { image: Image, src: Bounds, dst: Bounds, sampling: Sampling },
+  Svg { picture: Picture, dst: Bounds, tint: Option<Color> }, // vector document recorded at the size of dst
+  Picture(Picture), // drawing kept as skia recorded it, of a canvas element's painter or a cached subtree
+
+  // Scopes, each ends at the matching Pop
+  PushClip { bounds: Bounds, radii: [f32; 4] },
+  PushTransform([f32; 6]), // affine matrix: scale x, skew y, skew x, scale y, translate x, translate y
+  PushLayer { opacity: f32 },
+  Pop,
+}
+
+
+impl PartialEq for DisplayItem {
+  fn eq(&self, other: &Self) -> bool {
+    use DisplayItem::*;
+
+    match (self, other) {
+      (Quad(a), Quad(b)) => a == b,
+      (RoundedQuad { quad: a, radii: ra }, RoundedQuad { quad: b, radii: rb }) => a == b && ra == rb,
+      (Gradient { bounds: a, radii: ra, from: fa, to: ta, axis: xa }, Gradient { bounds: b, radii: rb, from: fb, to: tb, axis: xb }) =>
+        a == b && ra == rb && fa == fb && ta == tb && xa == xb,
+      (Border { bounds: a, radii: ra, widths: wa, color: ca }, Border { bounds: b, radii: rb, widths: wb, color: cb }) =>
+        a == b && ra == rb && wa == wb && ca == cb,
+      // A line shaped anew is painted again, even from the same text, font, size and colour
+      (Text { text: a, origin: oa, font: fa, size: sa, color: ca, paragraph: pa }, Text { text: b, origin: ob, font: fb, size: sb, color: cb, paragraph: pb }) =>
+        a == b && oa == ob && fa == fb && sa == sb && ca == cb && Rc::ptr_eq(pa, pb),
+      // Images and pictures are immutable, the same id means the same pixels
+      (Image { image: a, src: sa, dst: da, sampling: xa }, Image { image: b, src: sb, dst: db, sampling: xb }) =>
+        a.unique_id() == b.unique_id() && sa == sb && da == db && xa == xb,
+      (Svg { picture: a, dst: da, tint: ta }, Svg { picture: b, dst: db, tint: tb }) => a.unique_id() == b.unique_id() && da == db && ta == tb,
+      (Picture(a), Picture(b)) => a.unique_id() == b.unique_id(),
+      (PushClip { bounds: a, radii: ra }, PushClip { bounds: b, radii: rb }) => a == b && ra == rb,
+      (PushTransform(a), PushTransform(b)) => a == b,
+      (PushLayer { opacity: a }, PushLayer { opacity: b }) => a == b,
+      (Pop, Pop) => true,
+      _ => false,
+    }
+  }
+}
+
+impl DisplayItem {
+
+  /* Draws the item onto the canvas, a push saves the canvas state the matching Pop restores */
+  pub fn replay(&self, canvas: &Canvas) {
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true);
+
+    match self {
+      DisplayItem::Quad(quad) => {
+        paint.set_color(quad.color);
+        canvas.draw_rect(Rect::from(quad.bounds()), &paint);
+      }
+      DisplayItem::RoundedQuad { quad, radii } => {
+        paint.set_color(quad.color);
+        canvas.draw_rrect(rounded_rect(quad.bounds().into(), *radii, [0.0; 4]), &paint);
+      }
+      DisplayItem::Gradient { bounds, radii, from, to, axis } => {
+        let rect = Rect::from(*bounds);
+        let end = match axis {
+          Axis::Horizontal => (rect.right, rect.top),
+          Axis::Vertical => (rect.left, rect.bottom),
+        };
+        let colors = [skia_safe::Color::from(*from), skia_safe::Color::from(*to)];
+        paint.set_shader(Shader::linear_gradient(((rect.left, rect.top), end), &colors[..], None, TileMode::Clamp, None, None));
+        canvas.draw_rrect(rounded_rect(rect, *radii, [0.0; 4]), &paint);
+      }
+      DisplayItem::Border { bounds, radii, widths, color } => {
+        let rect = Rect::from(*bounds);
+        let [left, right, top, bottom] = *widths;
+        let inner = Rect::new(rect.left + left, rect.top + top, rect.right - right, rect.bottom - bottom);
+
+        paint.set_color(*color);
+        canvas.draw_drrect(rounded_rect(rect, *radii, [0.0; 4]), rounded_rect(inner, *radii, *widths), &paint);
+      }
+      DisplayItem::Text { origin, paragraph, .. } => {
+        paragraph.paint(canvas, *origin);
+      }
+      DisplayItem::Image { image, src, dst, sampling } => {
+        let src = Rect::from(*src);
+        canvas.draw_image_rect_with_sampling_options(image, Some((&src, SrcRectConstraint::Fast)), Rect::from(*dst), *sampling, &paint);
+      }
+      DisplayItem::Svg { picture, dst, tint } => {
+        // Tinting replaces the colour of every drawn pixel and keeps its coverage, which recolours monochrome icons
+        if let Some(tint) = tint {
+          paint.set_color_filter(color_filters::blend(skia_safe::Color::from(*tint), BlendMode::SrcIn));
+        }
+        canvas.draw_picture(picture, Some(&Matrix::translate((dst.x, dst.y))), tint.is_some().then_some(&paint));
+      }
+      DisplayItem::Picture(picture) => {
+        canvas.draw_picture(picture, None, None);
+      }
+      DisplayItem::PushClip { bounds, radii } => {
+        canvas.save();
+        canvas.clip_rrect(rounded_rect((*bounds).into(), *radii, [0.0; 4]), None, true);
+      }
+      DisplayItem::PushTransform(affine) => {
+        canvas.save();
+        canvas.concat(&Matrix::from_affine(affine));
+      }
+      DisplayItem::PushLayer { opacity } => {
+        canvas.save_layer_alpha_f(None, *opacity);
+      }
+      DisplayItem::Pop => {
+        canvas.restore();
+      }
+    }
+  }
+}
+
+
+/* Drawing operations of a frame in paint order, can be inspected, compared with the previous frame and replayed */
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct DisplayList {
+  items: Vec<DisplayItem>,
+}
+
+
+impl DisplayList {
+
+  pub fn new() -> Self { Default::default() }
+
+  pub fn push(&mut self, item: DisplayItem) {
+    self.items.push(item);
+  }
+
+  pub fn extend(&mut self, items: impl IntoIterator<Item = DisplayItem>) {
+    self.items.extend(items);
+  }
+
+  pub fn items(&self) -> &[DisplayItem] {
+    &self.items
+  }
+
+  pub fn len(&self) -> usize {
+    self.items.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.items.is_empty()
+  }
+
+  /* Positions of the items that differ from the list of the previous frame, including items only one list has */
+  pub fn diff(&self, previous: &DisplayList) -> Vec<usize> {
+    (0..self.items.len().max(previous.items.len()))
+      .filter(|&i| self.items.get(i) != previous.items.get(i))
+      .collect()
+  }
+
+  /* Draws the items onto any skia canvas, scopes left open are closed at the end */
+  pub fn replay(&self, canvas: &Canvas) {
+    let count = canvas.save_count();
+    for item in &self.items {
+      item.replay(canvas);
+    }
+    canvas.restore_to_count(count);
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use skia_safe::surfaces;
+
+  use super::*;
+  use crate::{gui::{container::Container, layout::{LayoutElement, Size}, style::Background}, testing::{lay_out, layout_of}};
+
+  #[test]
+  fn test_display_list() {
+    let mut root = Container::new("Root")
+      .width(Size::Max)
+      .height(Size::Max)
+      .background(skia_safe::Color::WHITE)
+      .horizontal(vec![
+        Container::new("Card").width(Size::Unit(2.0)).height(Size::Unit(2.0)).background(skia_safe::Color::RED).boxed(),
+      ]);
+    let root: &mut dyn LayoutElement = &mut root;
+    lay_out(root);
+
+    let list = root.display_list(10);
+    let white = Color { r: 255, g: 255, b: 255, a: 255 };
+    let red = Color { r: 255, g: 0, b: 0, a: 255 };
+    assert_eq!(list.items(), &[
+      DisplayItem::Quad(Quad2D::new(0.0, 0.0, 100.0, 100.0, white)),
+      DisplayItem::Quad(Quad2D::new(0.0, 0.0, 20.0, 20.0, red)),
+    ]);
+
+    // Only the changed item differs from the previous frame
+    layout_of(root, "Card").style.background = Background::Color(skia_safe::Color::BLUE);
+    lay_out(root);
+    let next = root.display_list(10);
+    assert_eq!(next.diff(&list), vec![1]);
+
+    let mut surface = surfaces::raster_n32_premul((40, 20)).unwrap();
+    list.replay(surface.canvas());
+    let pixels = surface.peek_pixels().unwrap();
+    assert_eq!(pixels.get_color((10, 10)), skia_safe::Color::RED);
+    assert_eq!(pixels.get_color((30, 10)), skia_safe::Color::WHITE);
+  }
+}
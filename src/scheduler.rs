@@ -0,0 +1,123 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex};
+
+use winit::event_loop::EventLoopProxy;
+
+
+#[derive(Default)]
+struct Shared {
+  pending : AtomicBool,
+  proxy : Mutex<Option<EventLoopProxy<()>>>, // set once the event loop runs
+}
+
+
+/* Asks for a new frame from any thread, waking the event loop if it sleeps. Elements get one through LayoutElement::connect */
+#[derive(Clone, Default)]
+pub struct RedrawHandle {
+  shared : Arc<Shared>,
+}
+
+
+impl RedrawHandle {
+
+  pub fn request_redraw(&self) {
+    self.shared.pending.store(true, Ordering::Release);
+    if let Some(proxy) = self.shared.proxy.lock().unwrap().as_ref() {
+      // Fails only when the event loop is gone, then there is nothing left to draw
+      let _ = proxy.send_event(());
+    }
+  }
+}
+
+
+/*
+Decides when a frame is drawn so the application sleeps while nothing changes.
+Frames are due after input, state changes, resizes and redraw requests, and as long as transitions run.
+*/
+pub struct FrameScheduler {
+  requested : bool,
+  animating : bool,
+  frames : u64,
+  handle : RedrawHandle,
+}
+
+
+impl Default for FrameScheduler {
+  fn default() -> Self {
+    // The first frame is always due
+    FrameScheduler { requested: true, animating: false, frames: 0, handle: RedrawHandle::default() }
+  }
+}
+
+impl FrameScheduler {
+
+  pub fn new() -> Self { Default::default() }
+
+  /* Handle elements and other threads use to request frames */
+  pub fn handle(&self) -> RedrawHandle {
+    self.handle.clone()
+  }
+
+  /* Lets the handles wake the event loop */
+  pub fn connect(&self, proxy : EventLoopProxy<()>) {
+    *self.handle.shared.proxy.lock().unwrap() = Some(proxy);
+  }
+
+  pub fn request(&mut self) {
+    self.requested = true;
+  }
+
+  /* Whether a frame has to be drawn, consumes the pending requests */
+  pub fn due(&mut self) -> bool {
+    let remote = self.handle.shared.pending.swap(false, Ordering::Acquire);
+    let due = self.requested || self.animating || remote;
+    self.requested = false;
+    due
+  }
+
+  /* Records a drawn frame, a running transition makes the next frame due right away */
+  pub fn presented(&mut self, animating : bool) {
+    self.frames += 1;
+    self.animating = animating;
+  }
+
+  /* Number of frames drawn so far */
+  pub fn frames(&self) -> u64 {
+    self.frames
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_frames_only_when_due() {
+    let mut scheduler = FrameScheduler::new();
+    let tick = |scheduler: &mut FrameScheduler, animating: bool| {
+      if scheduler.due() {
+        scheduler.presented(animating);
+      }
+    };
+
+    // First frame, then idle
+    for _ in 0..10 { tick(&mut scheduler, false); }
+    assert_eq!(scheduler.frames(), 1);
+
+    // Requests through the handle, from this and other threads, draw one frame each
+    scheduler.handle().request_redraw();
+    for _ in 0..10 { tick(&mut scheduler, false); }
+    let handle = scheduler.handle();
+    std::thread::spawn(move || handle.request_redraw()).join().unwrap();
+    for _ in 0..10 { tick(&mut scheduler, false); }
+    assert_eq!(scheduler.frames(), 3);
+
+    // A transition keeps drawing until it ends
+    scheduler.request();
+    tick(&mut scheduler, true);
+    tick(&mut scheduler, true);
+    tick(&mut scheduler, false);
+    tick(&mut scheduler, false);
+    assert_eq!(scheduler.frames(), 6);
+  }
+}
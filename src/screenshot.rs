@@ -0,0 +1,172 @@
+use std::{fmt, io, path::{Path, PathBuf}};
+
+use skia_safe::{Color, EncodedImageFormat, Image};
+
+use crate::{backend::{prepare, render, RasterBackend}, gui::{fonts::FontManager, image::ImageError, layout::LayoutElement, text::TextContext}};
+
+
+/* Failure to write a rendered file, shared by the screenshot and the document exports */
+#[derive(Debug)]
+pub struct WriteError {
+  pub path : PathBuf,
+  pub error : io::Error,
+}
+
+
+impl fmt::Display for WriteError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "could not write {}: {}", self.path.display(), self.error)
+  }
+}
+
+impl std::error::Error for WriteError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    Some(&self.error)
+  }
+}
+
+/* Writes the data to path, replacing the file if it exists */
+pub(crate) fn write_file(path : &Path, data : impl AsRef<[u8]>) -> Result<(), WriteError> {
+  std::fs::write(path, data).map_err(|error| WriteError { path: path.to_path_buf(), error })
+}
+
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+  Image(ImageError),
+  Surface(u32, u32), // physical size skia couldn't allocate a surface for
+  Encode,
+  Write(WriteError),
+}
+
+
+impl fmt::Display for ScreenshotError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ScreenshotError::Image(error) => error.fmt(f),
+      ScreenshotError::Surface(width, height) => write!(f, "could not create a {width}x{height} surface"),
+      ScreenshotError::Encode => write!(f, "could not encode the frame as PNG"),
+      ScreenshotError::Write(error) => error.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for ScreenshotError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ScreenshotError::Image(error) => Some(error),
+      ScreenshotError::Write(error) => Some(error),
+      _ => None,
+    }
+  }
+}
+
+impl From<ImageError> for ScreenshotError {
+  fn from(error: ImageError) -> Self { ScreenshotError::Image(error) }
+}
+
+impl From<WriteError> for ScreenshotError {
+  fn from(error: WriteError) -> Self { ScreenshotError::Write(error) }
+}
+
+
+/*
+Off-screen render of a layout tree, e.g. for documentation or bug reports.
+Width and height are in logical pixels, the image has the physical size for the scale factor.
+*/
+#[derive(Clone, Debug)]
+pub struct Screenshot {
+  pub width : u32,
+  pub height : u32,
+  pub scale_factor : f32,
+  pub unit_size : u32,
+  pub clear : Color,
+  pub text : TextContext, // system fonts unless a font manager is given
+}
+
+
+impl Screenshot {
+
+  pub fn new(width : u32, height : u32) -> Self {
+    Screenshot { width, height, scale_factor: 1.0, unit_size: 16, clear: Color::WHITE, text: TextContext::default() }
+  }
+
+  pub fn scale_factor(mut self, scale_factor : f32) -> Self {
+    self.scale_factor = scale_factor;
+    self
+  }
+
+  pub fn unit_size(mut self, unit_size : u32) -> Self {
+    self.unit_size = unit_size;
+    self
+  }
+
+  /* Shapes text with the families registered in the font manager */
+  pub fn fonts(mut self, fonts : &FontManager) -> Self {
+    self.text = TextContext::new(fonts.collection());
+    self
+  }
+
+  /* Colour the image is cleared to before the tree is painted, white by default */
+  pub fn clear(mut self, color : Color) -> Self {
+    self.clear = color;
+    self
+  }
+
+  /* Size of the image in physical pixels */
+  pub fn physical_size(&self) -> (u32, u32) {
+    (
+      (self.width as f32 * self.scale_factor).ceil() as u32,
+      (self.height as f32 * self.scale_factor).ceil() as u32,
+    )
+  }
+
+  /* Loads the images of the tree, lays it out and paints it on the CPU */
+  pub fn render(&self, root : &mut dyn LayoutElement) -> Result<Image, ScreenshotError> {
+    prepare(root, self.unit_size)?;
+
+    let (width, height) = self.physical_size();
+    let mut backend = RasterBackend::new(width, height).ok_or(ScreenshotError::Surface(width, height))?;
+    render(&mut backend, root, self.clear, self.scale_factor, self.unit_size, &self.text);
+    Ok(backend.snapshot())
+  }
+
+  /* Rendered tree encoded as PNG */
+  pub fn png(&self, root : &mut dyn LayoutElement) -> Result<Vec<u8>, ScreenshotError> {
+    let image = self.render(root)?;
+    let data = image.encode(None, EncodedImageFormat::PNG, None).ok_or(ScreenshotError::Encode)?;
+    Ok(data.as_bytes().to_vec())
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, root : &mut dyn LayoutElement, path : P) -> Result<(), ScreenshotError> {
+    let path = path.as_ref();
+    let png = self.png(root)?;
+    Ok(write_file(path, png)?)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::gui::{container::Container, image::decode, layout::Size};
+
+  #[test]
+  fn test_png() {
+    let mut root = Container::new("Root")
+      .width(Size::Unit(2.0))
+      .height(Size::Unit(2.0))
+      .background(Color::RED);
+
+    let screenshot = Screenshot::new(30, 20).scale_factor(1.5).unit_size(5).clear(Color::BLUE);
+    assert_eq!(screenshot.physical_size(), (45, 30));
+
+    let png = screenshot.png(&mut root).unwrap();
+    let image = decode(&png).unwrap();
+    assert_eq!((image.width(), image.height()), (45, 30));
+
+    let pixels = image.peek_pixels().unwrap();
+    assert_eq!(pixels.get_color((5, 5)), Color::RED);
+    assert_eq!(pixels.get_color((20, 5)), Color::BLUE);
+  }
+}
@@ -0,0 +1,192 @@
+use std::{collections::VecDeque, time::{Duration, Instant}};
+
+use skia_safe::{textlayout::FontCollection, Canvas, Color, Paint, Rect};
+
+use crate::gui::{cache::CacheStats, text::{shape_line, FontSpec, TextContext}};
+
+
+/* Frames the averages and percentiles are taken over */
+const WINDOW: usize = 120;
+
+/* Frame time at 60 Hz, marked in the overlay graph */
+const BUDGET: Duration = Duration::from_micros(16_667);
+
+const OVERLAY_SIZE: (f32, f32) = (2.0 * WINDOW as f32, 80.0);
+const OVERLAY_TEXT: f32 = 20.0;
+
+
+/* Time spent on the stages of one frame */
+#[derive(PartialEq, Clone, Copy, Default, Debug)]
+pub struct FrameTiming {
+  pub layout : Duration,   // layout, transitions and damage tracking
+  pub paint : Duration,
+  pub present : Duration,  // flushing to the GPU and swapping buffers
+  pub interval : Duration, // since the start of the previous frame
+}
+
+
+impl FrameTiming {
+
+  pub fn total(&self) -> Duration {
+    self.layout + self.paint + self.present
+  }
+}
+
+
+/* Timings of the last frames, measured by the application while drawing */
+#[derive(Default)]
+pub struct FrameStats {
+  timings : VecDeque<FrameTiming>,
+  frames : u64,
+  previous_start : Option<Instant>,
+  cache : CacheStats,
+}
+
+
+impl FrameStats {
+
+  pub fn new() -> Self { Default::default() }
+
+  /* Records the frame that started at start, its stages ended at the given instants */
+  pub fn record(&mut self, start : Instant, laid_out : Instant, painted : Instant, presented : Instant) {
+    let interval = self.previous_start.map_or(Duration::ZERO, |previous| start - previous);
+    self.previous_start = Some(start);
+
+    self.push(FrameTiming { layout: laid_out - start, paint: painted - laid_out, present: presented - painted, interval });
+  }
+
+  pub fn push(&mut self, timing : FrameTiming) {
+    if self.timings.len() == WINDOW {
+      self.timings.pop_front();
+    }
+    self.timings.push_back(timing);
+    self.frames += 1;
+  }
+
+  /* Picture cache use of the painted tree, see LayoutElement::cache_stats */
+  pub fn set_cache(&mut self, cache : CacheStats) {
+    self.cache = cache;
+  }
+
+  pub fn cache(&self) -> CacheStats {
+    self.cache
+  }
+
+  /* Number of frames recorded so far */
+  pub fn frames(&self) -> u64 {
+    self.frames
+  }
+
+  pub fn last(&self) -> Option<&FrameTiming> {
+    self.timings.back()
+  }
+
+  /* Timings of the last frames, oldest first */
+  pub fn timings(&self) -> impl Iterator<Item = &FrameTiming> {
+    self.timings.iter()
+  }
+
+  /* Mean of every stage over the last frames */
+  pub fn average(&self) -> FrameTiming {
+    let count = self.timings.len().max(1) as u32;
+    let sum = self.timings.iter().fold(FrameTiming::default(), |sum, timing| FrameTiming {
+      layout: sum.layout + timing.layout,
+      paint: sum.paint + timing.paint,
+      present: sum.present + timing.present,
+      interval: sum.interval + timing.interval,
+    });
+    FrameTiming { layout: sum.layout / count, paint: sum.paint / count, present: sum.present / count, interval: sum.interval / count }
+  }
+
+  /* Total frame time that the given fraction (e.g. 0.95) of the last frames stayed within */
+  pub fn percentile(&self, fraction : f32) -> Duration {
+    let mut totals: Vec<_> = self.timings.iter().map(FrameTiming::total).collect();
+    if totals.is_empty() { return Duration::ZERO }
+    totals.sort();
+
+    let rank = (fraction.clamp(0.0, 1.0) * totals.len() as f32).ceil() as usize;
+    totals[rank.clamp(1, totals.len()) - 1]
+  }
+
+  /* Frames per second, from the intervals between the last frames */
+  pub fn fps(&self) -> f32 {
+    // The first frame has no previous one
+    let intervals: Vec<_> = self.timings.iter().map(|t| t.interval).filter(|i| !i.is_zero()).collect();
+    if intervals.is_empty() { return 0.0 }
+    intervals.len() as f32 / intervals.iter().sum::<Duration>().as_secs_f32()
+  }
+
+  /* Area the overlay covers at origin */
+  pub fn overlay_rect(origin : (f32, f32)) -> Rect {
+    Rect::from_xywh(origin.0, origin.1, OVERLAY_SIZE.0, OVERLAY_SIZE.1)
+  }
+
+  /* Graph of the last frame times with a line at the 60 Hz budget, at origin in logical pixels, labelled with a font of the collection */
+  pub fn draw_overlay(&self, canvas : &Canvas, origin : (f32, f32), fonts : &FontCollection) {
+    let (x, y) = origin;
+    let (width, height) = OVERLAY_SIZE;
+    let graph = height - OVERLAY_TEXT;
+
+    let mut paint = Paint::default();
+    paint.set_color(Color::from_argb(220, 20, 20, 20));
+    canvas.draw_rect(Self::overlay_rect(origin), &paint);
+
+    // Bars stack layout, paint and present, twice the budget fills the graph
+    let scale = graph / (2.0 * BUDGET.as_secs_f32());
+    let colors = [Color::from_rgb(94, 129, 172), Color::from_rgb(163, 190, 140), Color::from_rgb(235, 203, 139)];
+    for (i, timing) in self.timings.iter().enumerate() {
+      let mut bottom = y + height;
+      for (stage, color) in [timing.layout, timing.paint, timing.present].into_iter().zip(colors) {
+        let top = (bottom - stage.as_secs_f32() * scale).max(y + OVERLAY_TEXT);
+        paint.set_color(color);
+        canvas.draw_rect(Rect::new(x + 2.0 * i as f32, top, x + 2.0 * i as f32 + 2.0, bottom), &paint);
+        bottom = top;
+      }
+    }
+
+    paint.set_color(Color::from_rgb(191, 97, 106));
+    let budget = y + height - BUDGET.as_secs_f32() * scale;
+    canvas.draw_rect(Rect::new(x, budget, x + width, budget + 1.0), &paint);
+
+    let text = format!(
+      "{:.0} fps  avg {:.1}  p95 {:.1} ms  cache {:.0}%",
+      self.fps(),
+      self.average().total().as_secs_f32() * 1000.0,
+      self.percentile(0.95).as_secs_f32() * 1000.0,
+      self.cache.hit_rate() * 100.0,
+    );
+    let spec = FontSpec { size: 12.0, ..Default::default() };
+    // Not scaled with the zoom, the graph keeps its size
+    let line = shape_line(&text, &spec, Color::WHITE, &TextContext::new(fonts.clone()));
+    line.paint(canvas, (x + 4.0, y + (OVERLAY_TEXT - line.height()) / 2.0));
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ms(ms : u64) -> Duration { Duration::from_millis(ms) }
+
+  #[test]
+  fn test_stats() {
+    let mut stats = FrameStats::new();
+    for i in 1..=10 {
+      stats.push(FrameTiming { layout: ms(1), paint: ms(i), present: ms(1), interval: ms(20) });
+    }
+
+    assert_eq!(stats.frames(), 10);
+    assert_eq!(stats.average().paint, Duration::from_micros(5500));
+    assert_eq!(stats.percentile(0.5), ms(7));
+    assert_eq!(stats.percentile(0.95), ms(12));
+    assert_eq!(stats.percentile(0.0), ms(3));
+    assert!((stats.fps() - 50.0).abs() < 0.01);
+
+    for _ in 0..WINDOW {
+      stats.push(FrameTiming::default());
+    }
+    assert_eq!(stats.timings().count(), WINDOW);
+    assert_eq!(stats.average(), FrameTiming::default());
+  }
+}
@@ -0,0 +1,20 @@
+/* Fixtures shared by the unit tests */
+
+use std::time::Instant;
+
+use crate::gui::{layout::{Layout, LayoutElement}, text::TextContext};
+
+
+/* Lays the tree out in a 100x100 window with a unit size of 10 and resolves its styles, as a frame would */
+pub fn lay_out(root : &mut dyn LayoutElement) {
+  root.calculate(100, 100, 10, &TextContext::default());
+  root.animate(Instant::now());
+}
+
+/* Layout of the element with the given name, panics if the tree has none */
+pub fn layout_of<'a>(root : &'a mut dyn LayoutElement, name : &str) -> &'a mut Layout {
+  match root.iter_mut().find(|element| element.name() == Some(name)) {
+    Some(element) => element.layout_mut(),
+    None => panic!("no element named {name}"),
+  }
+}
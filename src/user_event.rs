@@ -1,11 +1,36 @@
 
 
-
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum UserEvent {
   Quit,
   Click(f32, f32),
+  Press(f32, f32),
+  Release(f32, f32),
   Resize(u32, u32),
   MouseMoved(f32, f32),
+  MouseLeft,
   None,
-}
\ No newline at end of file
+}
+
+
+impl UserEvent {
+
+  /* Window coordinates of pointer events */
+  pub fn position(&self) -> Option<(f32, f32)> {
+    match *self {
+      UserEvent::Click(x, y) | UserEvent::Press(x, y) | UserEvent::Release(x, y) | UserEvent::MouseMoved(x, y) => Some((x, y)),
+      _ => None,
+    }
+  }
+
+  /* The same event at another position, other events are unchanged */
+  pub fn at(self, x: f32, y: f32) -> UserEvent {
+    match self {
+      UserEvent::Click(..) => UserEvent::Click(x, y),
+      UserEvent::Press(..) => UserEvent::Press(x, y),
+      UserEvent::Release(..) => UserEvent::Release(x, y),
+      UserEvent::MouseMoved(..) => UserEvent::MouseMoved(x, y),
+      event => event,
+    }
+  }
+}
@@ -0,0 +1,229 @@
+/*
+Golden image tests: element trees are rendered off-screen and compared with the reference PNGs in tests/golden.
+On a mismatch the rendered frame and an image marking the differing pixels are written next to the target dir.
+Run with REDWOOD_BLESS=1 to (re)write the references from the current rendering.
+*/
+
+use std::path::PathBuf;
+
+use redwood::{gui::{container::Container, fonts::FontManager, image::{decode, Fit, ImageElement, Sampling}, layout::{Axis, LayoutElement, Size}, text::TextElement}, screenshot::Screenshot};
+use skia_safe::{image::CachingHint, images, AlphaType, Color, ColorType, Data, EncodedImageFormat, Image, ImageInfo};
+
+
+const BLESS_VAR: &str = "REDWOOD_BLESS";
+
+
+/* How far a rendering may deviate from its reference, antialiasing differs slightly between platforms */
+#[derive(Clone, Copy, Debug)]
+struct Tolerance {
+  channel : u8,   // largest difference of a colour channel for pixels to count as equal
+  pixels : usize, // number of pixels allowed to differ
+}
+
+
+impl Default for Tolerance {
+  fn default() -> Self { Tolerance { channel: 2, pixels: 0 } }
+}
+
+
+/* Unpremultiplied RGBA pixels of an image */
+struct Pixels {
+  width : usize,
+  height : usize,
+  data : Vec<u8>,
+}
+
+
+impl Pixels {
+
+  fn of(image : &Image) -> Self {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let info = ImageInfo::new((width as i32, height as i32), ColorType::RGBA8888, AlphaType::Unpremul, None);
+    let mut data = vec![0u8; width * height * 4];
+    assert!(image.read_pixels(&info, &mut data, width * 4, (0, 0), CachingHint::Allow));
+    Pixels { width, height, data }
+  }
+
+  /* RGBA of the pixel, None outside of the image */
+  fn pixel(&self, x : usize, y : usize) -> Option<&[u8]> {
+    (x < self.width && y < self.height).then(|| &self.data[(y * self.width + x) * 4..][..4])
+  }
+
+  fn encode(&self) -> Vec<u8> {
+    let info = ImageInfo::new((self.width as i32, self.height as i32), ColorType::RGBA8888, AlphaType::Unpremul, None);
+    let image = images::raster_from_data(&info, Data::new_copy(&self.data), self.width * 4).unwrap();
+    image.encode(None, EncodedImageFormat::PNG, None).unwrap().as_bytes().to_vec()
+  }
+}
+
+
+/*
+Per pixel comparison, returns the number of differing pixels and an image marking them in red
+over a faded copy of the rendering. Images of different sizes are compared over the larger extent,
+pixels only one of them has count as differing.
+*/
+fn diff(actual : &Pixels, reference : &Pixels, channel : u8) -> (usize, Pixels) {
+  let (width, height) = (actual.width.max(reference.width), actual.height.max(reference.height));
+
+  let mut count = 0;
+  let mut marked = Vec::with_capacity(width * height * 4);
+  for y in 0..height {
+    for x in 0..width {
+      match (actual.pixel(x, y), reference.pixel(x, y)) {
+        (Some(a), Some(r)) if a.iter().zip(r).all(|(a, r)| a.abs_diff(*r) <= channel) => {
+          let gray = ((a[0] as u32 + a[1] as u32 + a[2] as u32) / 3 / 4 + 191) as u8;
+          marked.extend([gray, gray, gray, 255]);
+        }
+        _ => {
+          count += 1;
+          marked.extend([255, 0, 0, 255]);
+        }
+      }
+    }
+  }
+
+  (count, Pixels { width, height, data: marked })
+}
+
+
+/* Renders the tree and compares it with tests/golden/<name>.png */
+fn assert_golden(name : &str, root : &mut dyn LayoutElement, screenshot : Screenshot, tolerance : Tolerance) {
+  let reference_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{name}.png"));
+  let png = screenshot.png(root).unwrap();
+
+  if std::env::var_os(BLESS_VAR).is_some() {
+    std::fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+    std::fs::write(&reference_path, png).unwrap();
+    return;
+  }
+
+  let Ok(reference) = std::fs::read(&reference_path) else {
+    panic!("no reference {}, run with {BLESS_VAR}=1 to create it", reference_path.display());
+  };
+
+  let actual = Pixels::of(&decode(&png).unwrap());
+  let reference = Pixels::of(&decode(&reference).unwrap());
+  let (count, marked) = diff(&actual, &reference, tolerance.channel);
+
+  let resized = (actual.width, actual.height) != (reference.width, reference.height);
+
+  if resized || count > tolerance.pixels {
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&out).unwrap();
+    std::fs::write(out.join(format!("{name}.actual.png")), png).unwrap();
+    std::fs::write(out.join(format!("{name}.diff.png")), marked.encode()).unwrap();
+
+    if resized {
+      panic!(
+        "{name}: rendered {}x{}, {} is {}x{}, see {}",
+        actual.width, actual.height, reference_path.display(), reference.width, reference.height, out.display(),
+      );
+    }
+    panic!(
+      "{name}: {count} pixels differ from {} (tolerance {tolerance:?}), see {}",
+      reference_path.display(),
+      out.display(),
+    );
+  }
+}
+
+
+#[test]
+fn test_diff() {
+  let reference = Pixels { width: 2, height: 1, data: vec![10, 10, 10, 255, 200, 0, 0, 255] };
+  let actual = Pixels { width: 2, height: 1, data: vec![12, 10, 10, 255, 0, 0, 200, 255] };
+
+  let (count, marked) = diff(&actual, &reference, 2);
+  assert_eq!(count, 1);
+  assert_eq!(&marked.data[4..], &[255, 0, 0, 255]);
+  assert_eq!(diff(&actual, &reference, 255).0, 0);
+
+  // Pixels outside of the smaller image differ
+  let wider = Pixels { width: 3, height: 1, data: [reference.data.clone(), vec![0, 0, 0, 255]].concat() };
+  let (count, marked) = diff(&wider, &reference, 255);
+  assert_eq!((count, marked.width), (1, 3));
+}
+
+#[test]
+fn test_solid() {
+  let mut root = Container::new("Root")
+    .width(Size::Unit(4.0))
+    .height(Size::Unit(4.0))
+    .background(Color::RED);
+
+  assert_golden("solid", &mut root, Screenshot::new(20, 20).unit_size(4), Tolerance::default());
+}
+
+#[test]
+fn test_margin_scaled() {
+  let mut root = Container::new("Root")
+    .width(Size::Unit(6.0))
+    .height(Size::Unit(6.0))
+    .margin(1.0)
+    .background(Color::GREEN);
+
+  assert_golden("margin_scaled", &mut root, Screenshot::new(16, 16).scale_factor(2.0).unit_size(2), Tolerance::default());
+}
+
+#[test]
+fn test_rounded() {
+  let mut root = Container::new("Root")
+    .width(Size::Unit(8.0))
+    .height(Size::Unit(8.0))
+    .background(Color::RED)
+    .rounded(2.0);
+
+  // Only the antialiased corners may deviate, by how much depends on skia's coverage calculation
+  assert_golden("rounded", &mut root, Screenshot::new(32, 32).unit_size(4), Tolerance { channel: 24, pixels: 0 });
+}
+
+#[test]
+fn test_border() {
+  let mut root = Container::new("Root")
+    .width(Size::Unit(8.0))
+    .height(Size::Unit(6.0))
+    .background(Color::BLUE)
+    .border(1.0, Color::BLACK);
+
+  assert_golden("border", &mut root, Screenshot::new(32, 24).unit_size(4), Tolerance::default());
+}
+
+#[test]
+fn test_gradient() {
+  let mut root = Container::new("Root")
+    .width(Size::Unit(16.0))
+    .height(Size::Unit(2.0))
+    .gradient(Color::RED, Color::BLUE, Axis::Horizontal);
+
+  assert_golden("gradient", &mut root, Screenshot::new(64, 8).unit_size(4), Tolerance { channel: 3, pixels: 0 });
+}
+
+#[test]
+fn test_image() {
+  // Left half red, right half blue, letterboxed into the square
+  let mut root = ImageElement::new(&include_bytes!("../assets/images/swatch.png")[..])
+    .fit(Fit::Contain)
+    .sampling(Sampling::Nearest)
+    .width(Size::Unit(4.0))
+    .height(Size::Unit(4.0));
+
+  assert_golden("image", &mut root, Screenshot::new(16, 16).unit_size(4), Tolerance::default());
+}
+
+#[test]
+fn test_text() {
+  let mut fonts = FontManager::new();
+  fonts.register_bytes(include_bytes!("../assets/fonts/DejaVuSans.ttf")).unwrap();
+
+  let mut root = TextElement::new("HI")
+    .font_family("DejaVu Sans")
+    .font_size(32.0)
+    .color(Color::BLACK)
+    .width(Size::Unit(10.0))
+    .height(Size::Unit(10.0));
+
+  // Unhinted, glyphs are the exact outlines with skia's contrast and gamma correction of text masks, as the reference models them
+  let mut screenshot = Screenshot::new(40, 40).unit_size(4).fonts(&fonts);
+  screenshot.text.hinting = false;
+  assert_golden("text", &mut root, screenshot, Tolerance { channel: 24, pixels: 0 });
+}